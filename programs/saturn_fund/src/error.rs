@@ -8,4 +8,34 @@ pub enum SaturnFundError {
     SlippageExceeded,
     #[msg("Unauthorized access.")]
     Unauthorized,
+    #[msg("Trade exceeds the per-trade notional cap.")]
+    TradeNotionalExceeded,
+    #[msg("Trade exceeds the per-epoch notional cap.")]
+    EpochNotionalExceeded,
+    #[msg("Invalid trade limit configuration.")]
+    InvalidTradeLimits,
+    #[msg("No swap venue supports this trade.")]
+    NoSwapVenue,
+    #[msg("Aggregator route touches a fund vault outside the swap.")]
+    UnexpectedVaultInRoute,
+    #[msg("Invalid reward split configuration.")]
+    InvalidRewardSplit,
+    #[msg("Fee exceeds the allowed bounds.")]
+    InvalidFee,
+    #[msg("Users cannot refer themselves.")]
+    SelfReferral,
+    #[msg("User is not on the fund's allowlist.")]
+    NotAllowlisted,
+    #[msg("Vault still holds tokens.")]
+    VaultNotEmpty,
+    #[msg("Holding is still part of the allocation.")]
+    HoldingStillAllocated,
+    #[msg("Oracle price is older than the fund accepts.")]
+    StaleOraclePrice,
+    #[msg("Order is smaller than the market's lot size.")]
+    OrderBelowLotSize,
+    #[msg("The fund already has a share mint.")]
+    ShareMintAlreadySet,
+    #[msg("Vault is not a closable holding vault.")]
+    NotHoldingVault,
 }
@@ -0,0 +1,143 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+};
+use anchor_spl::token::{self, TokenAccount};
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Account as TokenAccount2022;
+use crate::error::SaturnFundError;
+use crate::instructions::fee_handling::is_token_program;
+use crate::instructions::raydium_integration::get_current_market_price_from_raydium;
+use crate::instructions::trade_limits::{min_amount_out, record_trade_notional, verify_amount_received};
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+
+/// Jupiter v6 aggregator program
+pub mod jupiter_v6 {
+    anchor_lang::declare_id!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+}
+
+#[derive(Accounts)]
+pub struct SwapViaAggregator<'info> {
+    #[account(mut)]
+    pub fund: Account<'info, Fund>,
+    #[account(constraint = keeper.key() == fund.keeper || keeper.key() == fund.owner)]
+    pub keeper: Signer<'info>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = source_vault.owner == fund_authority.key())]
+    pub source_vault: Account<'info, TokenAccount>,
+    #[account(mut, constraint = destination_vault.owner == fund_authority.key())]
+    pub destination_vault: Account<'info, TokenAccount>,
+    pub source_price_oracle: AccountInfo<'info>,
+    pub destination_price_oracle: AccountInfo<'info>,
+    #[account(address = jupiter_v6::ID)]
+    pub aggregator_program: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+    pub clock: Sysvar<'info, Clock>,
+    // The route's accounts are passed as remaining accounts, in the order the route expects
+}
+
+/// Executes a keeper-built aggregator route with the fund PDA as signer. The keeper is not
+/// trusted: the route may only move `amount_in` out of the source vault, may not touch any
+/// other fund vault, and must pay at least the oracle-derived minimum into the destination.
+pub fn swap_via_aggregator(ctx: Context<SwapViaAggregator>, amount_in: u64, route_data: Vec<u8>) -> ProgramResult {
+    let allocation = &ctx.accounts.allocation_pda;
+    let source_vault = &ctx.accounts.source_vault;
+    let destination_vault = &ctx.accounts.destination_vault;
+
+    // Both sides must be holdings of the fund, priced by their configured oracles
+    let source_holding = allocation.holding_tokens.iter()
+        .find(|holding| holding.vault == source_vault.key())
+        .ok_or(ProgramError::InvalidArgument)?;
+    let destination_holding = allocation.holding_tokens.iter()
+        .find(|holding| holding.vault == destination_vault.key())
+        .ok_or(ProgramError::InvalidArgument)?;
+    if source_holding.price_oracle != ctx.accounts.source_price_oracle.key()
+        || destination_holding.price_oracle != ctx.accounts.destination_price_oracle.key()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    check_route_accounts(ctx.remaining_accounts, &ctx.accounts.fund_authority.key(), source_vault, destination_vault)?;
+
+    let price_in = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.source_price_oracle)?;
+    let price_out = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.destination_price_oracle)?;
+    let minimum_amount_out = min_amount_out(&ctx.accounts.fund, amount_in, price_in, price_out)?;
+
+    let notional = amount_in
+        .checked_mul(price_in)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let now = ctx.accounts.clock.unix_timestamp;
+    record_trade_notional(&mut ctx.accounts.fund, notional, now)?;
+
+    let fund_authority_key = ctx.accounts.fund_authority.key();
+    let route_accounts = ctx.remaining_accounts.iter()
+        .map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer || *account.key == fund_authority_key,
+            is_writable: account.is_writable,
+        })
+        .collect();
+    let ix = Instruction {
+        program_id: jupiter_v6::ID,
+        accounts: route_accounts,
+        data: route_data,
+    };
+
+    let source_before = source_vault.amount;
+    let destination_before = destination_vault.amount;
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    let mut account_infos = ctx.remaining_accounts.to_vec();
+    account_infos.push(ctx.accounts.aggregator_program.clone());
+
+    msg!("Calling the aggregator route...");
+    invoke_signed(&ix, &account_infos, &[&seeds[..]])?;
+
+    let source_vault = &mut ctx.accounts.source_vault;
+    let destination_vault = &mut ctx.accounts.destination_vault;
+    source_vault.reload()?;
+    destination_vault.reload()?;
+
+    let spent = source_before.saturating_sub(source_vault.amount);
+    if spent > amount_in {
+        msg!("Route spent {} but only {} was allowed", spent, amount_in);
+        return Err(SaturnFundError::SlippageExceeded.into());
+    }
+    verify_amount_received(destination_before, destination_vault.amount, minimum_amount_out)?;
+
+    msg!("Aggregator swap completed: spent {}, received {}", spent, destination_vault.amount - destination_before);
+    Ok(())
+}
+
+/// Rejects routes that pass any fund-owned token account other than the two swap vaults,
+/// since the fund PDA signs the whole route. Covers accounts of both token programs.
+fn check_route_accounts(
+    accounts: &[AccountInfo],
+    fund_authority: &Pubkey,
+    source_vault: &Account<TokenAccount>,
+    destination_vault: &Account<TokenAccount>,
+) -> ProgramResult {
+    for account in accounts.iter() {
+        if !is_token_program(account.owner) || account.key == &source_vault.key() || account.key == &destination_vault.key() {
+            continue;
+        }
+
+        let data = account.try_borrow_data()?;
+        // Classic token accounts unpack as the base state without extensions
+        if let Ok(token_account) = StateWithExtensions::<TokenAccount2022>::unpack(&data) {
+            if token_account.base.owner == *fund_authority {
+                return Err(SaturnFundError::UnexpectedVaultInRoute.into());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, system_instruction};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Account as TokenAccount2022;
+use spl_transfer_hook_interface::{
+    collect_extra_account_metas_signer_seeds,
+    get_extra_account_metas_address_and_bump_seed,
+    instruction::ExecuteInstruction,
+};
+use crate::error::SaturnFundError;
+use crate::state::allowlist::{Allowlist, ALLOWLIST_SEED};
+use crate::state::fund::Fund;
+
+// Positions in the transfer hook's Execute accounts: source, mint, destination, owner,
+// extra account metas, then the extra accounts below
+const HOOK_MINT_INDEX: usize = 1;
+const HOOK_DESTINATION_INDEX: usize = 2;
+const HOOK_FUND_INDEX: u8 = 5;
+const HOOK_ALLOWLIST_INDEX: usize = 6;
+
+#[derive(Accounts)]
+pub struct UpdateRestrictions<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+}
+
+/// Switches restricted mode, in which only allowlisted users can mint and redeem
+pub fn set_restricted(ctx: Context<UpdateRestrictions>, restricted: bool) -> ProgramResult {
+    ctx.accounts.fund.restricted = restricted;
+
+    msg!("Restricted mode {}", if restricted { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+pub fn set_compliance_authority(ctx: Context<UpdateRestrictions>, compliance_authority: Pubkey) -> ProgramResult {
+    ctx.accounts.fund.compliance_authority = compliance_authority;
+
+    msg!("Compliance authority set to {}", compliance_authority);
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey, bump: u8)]
+pub struct AddToAllowlist<'info> {
+    #[account(has_one = compliance_authority)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub compliance_authority: Signer<'info>,
+    #[account(
+        init,
+        seeds = [ALLOWLIST_SEED, fund.key().as_ref(), user.as_ref()],
+        bump = bump,
+        payer = compliance_authority,
+        space = 8 + Allowlist::LEN,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn add_to_allowlist(ctx: Context<AddToAllowlist>, user: Pubkey, bump: u8) -> ProgramResult {
+    let entry = &mut ctx.accounts.allowlist;
+    entry.fund = ctx.accounts.fund.key();
+    entry.user = user;
+    entry.added_at = ctx.accounts.clock.unix_timestamp;
+    entry.bump = bump;
+
+    msg!("User {} added to the allowlist", user);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromAllowlist<'info> {
+    #[account(has_one = compliance_authority)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub compliance_authority: Signer<'info>,
+    #[account(
+        mut,
+        close = compliance_authority,
+        seeds = [ALLOWLIST_SEED, fund.key().as_ref(), allowlist.user.as_ref()],
+        bump = allowlist.bump,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+}
+
+pub fn remove_from_allowlist(ctx: Context<RemoveFromAllowlist>) -> ProgramResult {
+    msg!("User {} removed from the allowlist", ctx.accounts.allowlist.user);
+    Ok(())
+}
+
+/// Whether `user` holds an allowlist entry for the fund among `accounts`
+pub fn is_allowlisted<'info>(
+    program_id: &Pubkey,
+    fund: &Account<'info, Fund>,
+    accounts: &[AccountInfo<'info>],
+    user: &Pubkey,
+) -> Result<bool, ProgramError> {
+    let fund_key = fund.key();
+    let entry_key = Pubkey::find_program_address(&[ALLOWLIST_SEED, fund_key.as_ref(), user.as_ref()], program_id).0;
+
+    let entry_info = match accounts.iter().find(|account| *account.key == entry_key) {
+        Some(entry_info) => entry_info,
+        None => return Ok(false),
+    };
+    if entry_info.owner != program_id {
+        return Ok(false);
+    }
+
+    let entry = Account::<Allowlist>::try_from(entry_info)?;
+    Ok(entry.fund == fund_key && entry.user == *user)
+}
+
+#[derive(Accounts)]
+pub struct InitializeAllowlistHook<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(constraint = mint.key() == fund.share_mint)]
+    pub mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub extra_account_metas: AccountInfo<'info>, // Validation account Token-2022 reads to resolve the hook's accounts
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Sets up this program as the share mint's transfer hook. Every transfer then carries the fund
+/// and the allowlist entry of the destination's owner, so restricted funds can reject transfers
+/// to users outside the allowlist.
+pub fn initialize_allowlist_hook(ctx: Context<InitializeAllowlistHook>) -> ProgramResult {
+    let mint_key = ctx.accounts.mint.key();
+    let (metas_address, bump) = get_extra_account_metas_address_and_bump_seed(&mint_key, ctx.program_id);
+    if metas_address != ctx.accounts.extra_account_metas.key() {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let extra_metas = [
+        ExtraAccountMeta::new_with_pubkey(&ctx.accounts.fund.key(), false, false)?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: ALLOWLIST_SEED.to_vec() },
+                Seed::AccountKey { index: HOOK_FUND_INDEX },
+                // Owner field of the destination token account
+                Seed::AccountData { account_index: HOOK_DESTINATION_INDEX as u8, data_index: 32, length: 32 },
+            ],
+            false,
+            false,
+        )?,
+    ];
+
+    let space = ExtraAccountMetaList::size_of(extra_metas.len())?;
+    let lamports = ctx.accounts.rent.minimum_balance(space);
+    let bump_seed = [bump];
+    let signer_seeds = collect_extra_account_metas_signer_seeds(&mint_key, &bump_seed);
+    invoke_signed(
+        &system_instruction::create_account(ctx.accounts.owner.key, &metas_address, lamports, space as u64, ctx.program_id),
+        &[
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.extra_account_metas.clone(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        &[&signer_seeds],
+    )?;
+
+    let mut data = ctx.accounts.extra_account_metas.try_borrow_mut_data()?;
+    ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_metas)?;
+
+    msg!("Allowlist transfer hook initialized for {}", mint_key);
+    Ok(())
+}
+
+/// Token-2022 transfer hook `Execute` handler. In restricted mode the destination's owner
+/// must hold an allowlist entry; otherwise every transfer passes.
+pub fn transfer_hook_execute(program_id: &Pubkey, accounts: &[AccountInfo], _instruction_data: &[u8]) -> ProgramResult {
+    if accounts.len() <= HOOK_ALLOWLIST_INDEX {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let fund_info = &accounts[HOOK_FUND_INDEX as usize];
+    if fund_info.owner != program_id {
+        return Err(ProgramError::IllegalOwner);
+    }
+    let fund = Account::<Fund>::try_from(fund_info)?;
+    if fund.share_mint != *accounts[HOOK_MINT_INDEX].key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if !fund.restricted {
+        return Ok(());
+    }
+
+    let destination_owner = {
+        let data = accounts[HOOK_DESTINATION_INDEX].try_borrow_data()?;
+        StateWithExtensions::<TokenAccount2022>::unpack(&data)?.base.owner
+    };
+    if !is_allowlisted(program_id, &fund, &accounts[HOOK_ALLOWLIST_INDEX..=HOOK_ALLOWLIST_INDEX], &destination_owner)? {
+        return Err(SaturnFundError::NotAllowlisted.into());
+    }
+
+    Ok(())
+}
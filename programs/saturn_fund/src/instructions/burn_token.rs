@@ -1,173 +1,243 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Burn, TokenAccount, Transfer};
+use anchor_lang::solana_program::program::invoke;
+use anchor_spl::token::{self, spl_token::native_mint, TokenAccount, Transfer};
+use crate::error::SaturnFundError;
+use crate::instructions::calculate_price_of_fund::{get_fund_value, value_of_shares};
+use crate::instructions::fee_handling::{accrue_management_fee, check_share_account, is_token_program, redeem_fee_for, share_supply, ShareMint};
+use crate::instructions::fund_operation::enforce_transfer_restrictions;
+use crate::instructions::native_sol::{unwrap_sol, UnwrapSol};
+use crate::instructions::raydium_integration::{get_current_market_price_from_raydium, token_account_amount};
+use crate::instructions::swap_venue::{parse_venues, route_swap, SwapLeg};
+use crate::instructions::trade_limits::min_amount_out;
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+use crate::state::position::{UserPosition, USER_POSITION_SEED};
 
 // Define the context for the BurnToken instruction
 #[derive(Accounts)]
 pub struct BurnToken<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
+    #[account(mut, constraint = *user_token_account.owner == share_token_program.key())]
+    pub user_token_account: AccountInfo<'info>, // Holds the $STRN redeemed, under whichever program owns the share mint
+    #[account(mut, constraint = holding_account.mint == native_mint::ID && holding_account.owner == fund_authority.key())]
+    pub holding_account: Account<'info, TokenAccount>, // Fund wSOL vault paying redemptions
+    #[account(mut, constraint = fund_account.mint == native_mint::ID && fund_account.owner == fund_authority.key())]
+    pub fund_account: Account<'info, TokenAccount>, // Fund wSOL vault topping up the holding account
+    pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(address = native_mint::ID)]
+    pub native_mint: AccountInfo<'info>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub unwrap_account: Signer<'info>, // Fresh keypair, the temporary wSOL account redemptions are unwrapped through
     #[account(mut)]
-    pub holding_account: Account<'info, TokenAccount>,
+    pub fund: Account<'info, Fund>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = liquidation_vault.owner == fund_authority.key())]
+    pub liquidation_vault: Account<'info, TokenAccount>, // Holding sold to cover redemptions
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>, // Oracles configured for each holding
+    #[account(constraint = allocation_pda.price_oracle(&liquidation_vault.mint) == Some(liquidation_price_oracle.key()))]
+    pub liquidation_price_oracle: AccountInfo<'info>,
+    #[account(constraint = allocation_pda.price_oracle(&holding_account.mint) == Some(sol_price_oracle.key()))]
+    pub sol_price_oracle: AccountInfo<'info>,
+    #[account(mut, constraint = mint.key() == fund.share_mint && *mint.owner == share_token_program.key())]
+    pub mint: AccountInfo<'info>, // $STRN mint
+    #[account(constraint = is_token_program(&share_token_program.key()))]
+    pub share_token_program: AccountInfo<'info>, // SPL Token or Token-2022, whichever owns the share mint
     #[account(mut)]
-    pub fund_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, token::Token>,
+    pub fee_recipient: AccountInfo<'info>, // Receives accrued management fees
+    pub clock: Sysvar<'info, Clock>,
+    #[account(mut, constraint = fee_account.key() == fund.fee_vault && fee_account.mint == native_mint::ID)]
+    pub fee_account: Account<'info, TokenAccount>, // Fee vault, receives the redeem fee
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, fund.key().as_ref(), user.key().as_ref()],
+        bump = user_position.bump,
+    )]
+    pub user_position: Account<'info, UserPosition>, // Deposit time deciding the redeem fee
+    // The vaults and oracles valuing the fund, swap venue account groups, and the user's allowlist entry
+    // in restricted mode are passed as remaining accounts
 }
 
+/// Burns `amount` $STRN and pays the user out in native SOL unwrapped from the fund's wSOL vaults
 pub fn handler(ctx: Context<BurnToken>, amount: u64) -> ProgramResult {
-    let user_token_account = &ctx.accounts.user_token_account;
+    enforce_transfer_restrictions(ctx.program_id, &ctx.accounts.fund, ctx.remaining_accounts, &ctx.accounts.user.key())?;
+    check_share_account(&ctx.accounts.fund, ctx.accounts.share_token_program.key, &ctx.accounts.user_token_account)?;
+
+    // Bring fees up to date before shares are redeemed
+    let share_mint = ShareMint {
+        mint: ctx.accounts.mint.clone(),
+        fee_recipient: ctx.accounts.fee_recipient.clone(),
+        fund_authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.share_token_program.clone(),
+    };
+    accrue_management_fee(&mut ctx.accounts.fund, &share_mint, ctx.accounts.clock.unix_timestamp)?;
+
     let holding_account = &ctx.accounts.holding_account;
     let fund_account = &ctx.accounts.fund_account;
     let token_program = &ctx.accounts.token_program;
 
-    // Step 1: Receive $STRN tokens from the user
-    token::burn(
-        CpiContext::new(
-            token_program.to_account_info(),
-            Burn {
-                to: user_token_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            },
-        ),
-        amount,
-    )?;
+    // Step 1: Calculate the equivalent amount of Solana, net of the redeem fee the fund keeps.
+    // The fee decays with how long the user has held their shares.
+    let gross_solana = calculate_solana_equivalent(&ctx, amount)?;
+    let now = ctx.accounts.clock.unix_timestamp;
+    let fee = redeem_fee_for(&ctx.accounts.fund, &ctx.accounts.user_position, amount, gross_solana, now)?;
+    let solana_equivalent = gross_solana - fee;
+    if fee > 0 {
+        collect_redeem_fee(&ctx, fee)?;
+    }
 
-    // Step 2: Calculate the equivalent amount of Solana
-    let solana_equivalent = calculate_solana_equivalent(amount)?;
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
 
-    // Step 3: Check if the holding account has enough Solana
-    if holding_account.amount >= solana_equivalent {
-        // Step 4a: Transfer Solana to the user
-        token::transfer(
-            CpiContext::new_with_signer(
-                token_program.to_account_info(),
-                Transfer {
-                    from: holding_account.to_account_info(),
-                    to: user_token_account.to_account_info(),
-                    authority: ctx.accounts.program_authority.to_account_info().clone(),
-                },
-                &[&ctx.accounts.program_authority.to_account_info().key],
-            ),
-            solana_equivalent,
-        )?;
-    } else {
-        // Step 4b: Handle partial liquidation of the fund account
+    // Step 2: Top up the holding account when it can't cover the redemption
+    if holding_account.amount < solana_equivalent {
         let required_amount = solana_equivalent - holding_account.amount;
         let half_required_amount = required_amount / 2;
 
-        // Transfer 50% from holding tokens
-        liquidate_holding_tokens(ctx, half_required_amount)?;
+        // Raise half by selling holding tokens
+        liquidate_holding_tokens(&ctx, half_required_amount)?;
 
-        // Transfer 50% from Solana
+        // And the rest from the fund's Solana
         token::transfer(
             CpiContext::new_with_signer(
                 token_program.to_account_info(),
                 Transfer {
                     from: fund_account.to_account_info(),
                     to: holding_account.to_account_info(),
-                    authority: ctx.accounts.program_authority.to_account_info().clone(),
+                    authority: ctx.accounts.fund_authority.clone(),
                 },
-                &[&ctx.accounts.program_authority.to_account_info().key],
+                &[&seeds[..]],
             ),
-            half_required_amount,
+            required_amount - half_required_amount,
         )?;
 
-        // Transfer the remaining amount to the user
-        token::transfer(
-            CpiContext::new_with_signer(
-                token_program.to_account_info(),
-                Transfer {
-                    from: holding_account.to_account_info(),
-                    to: user_token_account.to_account_info(),
-                    authority: ctx.accounts.program_authority.to_account_info(),
-                },
-                &[&ctx.accounts.program_authority.to_account_info().key],
-            ),
-            holding_account.amount, // Transfer whatever is left in the holding account
-        )?;
+        // The user is paid in full or not at all
+        let available = token_account_amount(&holding_account.to_account_info())?;
+        if available < solana_equivalent {
+            msg!("Holding account has {} after liquidation, {} is owed", available, solana_equivalent);
+            return Err(SaturnFundError::InsufficientFunds.into());
+        }
     }
 
-    // Step 5: Burn the $STRN tokens received from the user
-    token::burn(
-        CpiContext::new(
-            token_program.to_account_info(),
-            Burn {
-                to: user_token_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            },
-        ),
-        amount,
-    )?;
+    // Step 3: Burn the $STRN tokens redeemed
+    burn_shares(&ctx, amount)?;
+    ctx.accounts.user_position.record_redemption(amount);
 
-    Ok(())
+    // Step 4: Pay the user in native SOL
+    pay_out_sol(&ctx, solana_equivalent, &[&seeds[..]])
+}
+
+// Burns `amount` $STRN from the user's account under the program owning the share mint
+fn burn_shares(ctx: &Context<BurnToken>, amount: u64) -> ProgramResult {
+    invoke(
+        &spl_token_2022::instruction::burn(
+            ctx.accounts.share_token_program.key,
+            ctx.accounts.user_token_account.key,
+            ctx.accounts.mint.key,
+            ctx.accounts.user.key,
+            &[],
+            amount,
+        )?,
+        &[
+            ctx.accounts.user_token_account.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.share_token_program.clone(),
+        ],
+    )
 }
 
 // Helper function to calculate the equivalent amount of Solana
-fn calculate_solana_equivalent(amount: u64) -> Result<u64, ProgramError> {
-    // Assuming `FUND_PRICE_PER_UNIT` is the price of one unit of the fund in terms of Solana
-    const FUND_PRICE_PER_UNIT: u64 = 10; 
+// Lamports `amount` $STRN redeem for at the fund's current value per share, net of vesting profit
+fn calculate_solana_equivalent(ctx: &Context<BurnToken>, amount: u64) -> Result<u64, ProgramError> {
+    let now = ctx.accounts.clock.unix_timestamp;
+    let fund_value = get_fund_value(&ctx.accounts.fund, &ctx.accounts.allocation_pda, ctx.remaining_accounts, now)?;
+    let supply = share_supply(&ctx.accounts.mint)?;
+    let redeemed_value = value_of_shares(fund_value, supply, amount)?;
+
+    let sol_price = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.sol_price_oracle)?;
+    if sol_price == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok(redeemed_value / sol_price)
+}
+
+// Unwraps `amount` wSOL from the holding account and sends it to the user as native SOL
+fn pay_out_sol(ctx: &Context<BurnToken>, amount: u64, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let accounts = UnwrapSol {
+        vault: ctx.accounts.holding_account.to_account_info(),
+        unwrap_account: ctx.accounts.unwrap_account.to_account_info(),
+        recipient: ctx.accounts.user.to_account_info(),
+        native_mint: ctx.accounts.native_mint.clone(),
+        fund_authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        rent: ctx.accounts.rent.clone(),
+    };
 
-    let solana_equivalent = amount * FUND_PRICE_PER_UNIT;
-    Ok(solana_equivalent)
+    unwrap_sol(&accounts, amount, signer_seeds)
+}
+
+// Moves the redeem fee from the fund's Solana account to the fee account
+fn collect_redeem_fee(ctx: &Context<BurnToken>, fee: u64) -> ProgramResult {
+    let fund = &ctx.accounts.fund;
+    let fund_key = fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[fund.authority_bump]];
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.fund_account.to_account_info(),
+                to: ctx.accounts.fee_account.to_account_info(),
+                authority: ctx.accounts.fund_authority.clone(),
+            },
+            &[&seeds[..]],
+        ),
+        fee,
+    )
 }
 
 // Helper function to liquidate holding tokens
-fn liquidate_holding_tokens(ctx: Context<BurnToken>, amount: u64) -> ProgramResult {
-    // Assuming there is a function `swap_tokens_for_solana` that handles the swap
+fn liquidate_holding_tokens(ctx: &Context<BurnToken>, amount: u64) -> ProgramResult {
     swap_tokens_for_solana(ctx, amount)?;
 
     Ok(())
 }
 
-// Mock-up of the swap function (you will need to implement this based on your specific requirements)
-fn swap_tokens_for_solana(ctx: Context<BurnToken>, amount: u64) -> ProgramResult {
-    // Define the DEX program ID
-    let dex_program_id = Pubkey::from_str("EnterDEXProgramIDHere").unwrap();
-
-    // Define the market and the open orders accounts
-    let market = Pubkey::from_str("EnterMarketPubkeyHere").unwrap();
-    let open_orders = Pubkey::from_str("EnterOpenOrdersPubkeyHere").unwrap();
-
-    // Define the source and destination token accounts
-    let source_token_account = ctx.accounts.holding_account.to_account_info();
-    let destination_token_account = ctx.accounts.user_token_account.to_account_info();
-
-    // Define the user's wallet account, which will be the authority
-    let user_wallet = ctx.accounts.user.to_account_info();
-
-    // Create a CPI context for the DEX swap
-    let cpi_accounts = Swap {
-        market: market,
-        open_orders: open_orders,
-        source_token_account: source_token_account,
-        destination_token_account: destination_token_account,
-        user_wallet: user_wallet,
+// Sells enough of the liquidation vault's holding to raise `amount` SOL, on the best-quoting venue
+fn swap_tokens_for_solana(ctx: &Context<BurnToken>, amount: u64) -> ProgramResult {
+    let fund = &ctx.accounts.fund;
+    let fund_key = fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[fund.authority_bump]];
+
+    let holding_price = get_current_market_price_from_raydium(fund, &ctx.accounts.liquidation_price_oracle)?;
+    let sol_price = get_current_market_price_from_raydium(fund, &ctx.accounts.sol_price_oracle)?;
+    if holding_price == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let amount_in = u64::try_from(amount as u128 * sol_price as u128 / holding_price as u128)
+        .map_err(|_| ProgramError::InvalidArgument)?;
+    let minimum_amount_out = min_amount_out(fund, amount_in, holding_price, sol_price)?;
+
+    let venues = parse_venues(ctx.program_id, &fund_key, ctx.remaining_accounts)?;
+    let leg = SwapLeg {
+        input_mint: ctx.accounts.liquidation_vault.mint,
+        output_mint: ctx.accounts.holding_account.mint,
+        source: ctx.accounts.liquidation_vault.to_account_info(),
+        destination: ctx.accounts.holding_account.to_account_info(),
+        authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.token_program.to_account_info(),
     };
-    let cpi_program = CpiProgram::new(&dex_program_id, &cpi_accounts);
-
-    // Assuming `swap` is a method provided by the DEX program
-    let swap_instruction = dex::instruction::swap(
-        &dex_program_id,
-        &market,
-        &open_orders,
-        &source_token_account.key(),
-        &destination_token_account.key(),
-        &user_wallet.key(),
-        amount,
-    )?;
-
-    // Perform the CPI call to the DEX program
-    anchor_lang::solana_program::program::invoke(
-        &swap_instruction,
-        &[
-            market.to_account_info(),
-            open_orders.to_account_info(),
-            source_token_account,
-            destination_token_account,
-            user_wallet,
-        ],
-    )?;
 
-    Ok(())
-}
\ No newline at end of file
+    route_swap(&venues, &leg, amount_in, minimum_amount_out, &[&seeds[..]])
+}
@@ -1,39 +1,30 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
-use crate::state::FundAccount;
+use crate::instructions::fee_handling::share_supply;
+use crate::instructions::manage_holdings::get_holdings_value;
+use crate::instructions::raydium_integration::get_liquidity_value;
+use crate::state::allocation::Allocation;
+use crate::state::fund::Fund;
 
 #[derive(Accounts)]
 pub struct CalculatePriceOfFund<'info> {
-    #[account(mut)]
-    pub fund_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub holding_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    pub fund: Account<'info, Fund>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(address = fund.share_mint)]
+    pub mint: AccountInfo<'info>, // $STRN mint, under either token program
+    pub clock: Sysvar<'info, Clock>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults, LP vaults, pool vaults and oracles referenced by the allocation
 }
 
 pub fn handler(ctx: Context<CalculatePriceOfFund>) -> ProgramResult {
-    let fund_account = &ctx.accounts.fund_account;
-    let holding_account = &ctx.accounts.holding_account;
-    let mint = &ctx.accounts.mint;
-
-    // Step 1: Get how many liquidity pool tokens are held by the fund account
-    let liquidity_pool_tokens = fund_account.amount;
-
-    // Step 2: Calculate how many of the underlying tokens are equivalent to that number of liquidity pool tokens
-    let underlying_tokens = calculate_underlying_tokens(liquidity_pool_tokens)?;
-
-    // Step 3: Get the current market price of those tokens
-    let market_price = get_current_market_price()?;
-
-    // Step 4: Calculate the value of those tokens
-    let value_of_tokens = underlying_tokens * market_price;
-
-    // Step 5: Sum to get the total market value of the fund and also add the value of the holding account
-    let total_market_value = value_of_tokens + holding_account.amount;
+    let now = ctx.accounts.clock.unix_timestamp;
+    let total_market_value = get_fund_value(&ctx.accounts.fund, &ctx.accounts.allocation_pda, &ctx.accounts.token_accounts, now)?;
 
     // Get the number of tokens in circulation from the mint account
-    let tokens_in_circulation = mint.supply;
+    let tokens_in_circulation = share_supply(&ctx.accounts.mint)?;
+    if tokens_in_circulation == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
 
     // Calculate the price of the fund
     let price_of_fund = total_market_value / tokens_in_circulation;
@@ -43,17 +34,35 @@ pub fn handler(ctx: Context<CalculatePriceOfFund>) -> ProgramResult {
     Ok(())
 }
 
-// Helper function to calculate the equivalent amount of underlying tokens
-fn calculate_underlying_tokens(liquidity_pool_tokens: u64) -> Result<u64, ProgramError> {
-    // logic: Assume each liquidity pool token is equivalent to 10 underlying tokens
-    let underlying_tokens = liquidity_pool_tokens * 10;
-    Ok(underlying_tokens) 
+/// Total market value of the fund: every holding plus every LP position, staked or not,
+/// less compounded rewards that are still vesting
+pub fn get_fund_value(fund: &Fund, allocation: &Allocation, accounts: &[AccountInfo], now: i64) -> Result<u64, ProgramError> {
+    let gross_value = get_holdings_value(fund, allocation, accounts)?
+        .checked_add(get_liquidity_value(fund, allocation, accounts)?)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    Ok(gross_value.saturating_sub(fund.current_locked_profit(now)))
 }
 
-// Helper function to get the current market price of the tokens
-fn get_current_market_price() -> Result<u64, ProgramError> {
-    // Simulated market price for demonstration purposes
-    let market_price: u64 = 100; // Assume the market price is 100 units
+/// $STRN issued for a deposit worth `deposit_value`, at the fund's value per share before the deposit.
+/// The first deposit into an empty fund is issued one share per unit of value.
+pub fn shares_for_deposit(fund_value: u64, supply: u64, deposit_value: u64) -> Result<u64, ProgramError> {
+    if supply == 0 {
+        return Ok(deposit_value);
+    }
+    if fund_value == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    u64::try_from(deposit_value as u128 * supply as u128 / fund_value as u128)
+        .map_err(|_| ProgramError::InvalidArgument)
+}
 
-    Ok(market_price)
-}
\ No newline at end of file
+/// Share of `fund_value` that `shares` out of `supply` redeem for
+pub fn value_of_shares(fund_value: u64, supply: u64, shares: u64) -> Result<u64, ProgramError> {
+    if supply == 0 || shares > supply {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok((fund_value as u128 * shares as u128 / supply as u128) as u64)
+}
@@ -1,118 +1,270 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, TokenAccount, Transfer};
+use crate::error::SaturnFundError;
+use crate::instructions::manage_holdings::{build_rebalance_plan, find_account_by_key, TradeSide};
+use crate::instructions::raydium_farm::{check_configured_farm, harvest_farm_rewards, RaydiumFarm};
+use crate::instructions::raydium_integration::{get_current_market_price_from_raydium, token_account_amount};
+use crate::instructions::swap_venue::{parse_venues, route_swap, SwapLeg};
+use crate::instructions::trade_limits::{min_amount_out, record_trade_notional};
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, RewardRecipient, FUND_AUTHORITY_SEED};
+use crate::BPS_DENOMINATOR;
+
+/// Upper bound on configured reward recipients, keeping the split within compute limits
+pub const MAX_REWARD_RECIPIENTS: usize = 8;
 
 #[derive(Accounts)]
 pub struct CollectLiquidityPoolRewards<'info> {
     #[account(mut, has_one = owner)]
     pub fund: Account<'info, Fund>,
     pub owner: Signer<'info>,
-    #[account(mut)]
-    pub reward_destination: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub fund_token_account: Account<'info, TokenAccount>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(mut, constraint = fund_token_account.key() == farm.fund_reward_vault.key())]
+    pub fund_token_account: Account<'info, TokenAccount>, // Fund's reward vault, filled by the harvest
     pub token_program: Program<'info, token::Token>,
-    #[account(mut)]
-    pub allocation: Account<'info, Allocation>,  // Added reference to Allocation
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+        constraint = fund_authority.key() == farm.fund_authority.key(),
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub farm: RaydiumFarm<'info>,
+    #[account(constraint = allocation_pda.price_oracle(&fund_token_account.mint) == Some(reward_price_oracle.key()))]
+    pub reward_price_oracle: AccountInfo<'info>, // Prices the reward token as configured for its holding
+    pub clock: Sysvar<'info, Clock>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Reward recipient accounts, plus holding vaults, oracles and swap venue groups when compounding
 }
 
-pub fn collect_liquidity_pool_rewards(ctx: Context<CollectLiquidityPoolRewards>) -> Result<()> {
-    let fund = &ctx.accounts.fund;
-    let owner = &ctx.accounts.owner;
-    let reward_destination = &ctx.accounts.reward_destination;
-    let fund_token_account = &ctx.accounts.fund_token_account;
-    let allocation = &ctx.accounts.allocation;  // Added allocation account
-
-    // Ensure the caller is the owner
-    require!(fund.owner == *owner.key, ErrorCode::Unauthorized);
-
-    // Collect rewards from Raydium
-    let rewards = collect_rewards_from_raydium()?;
-
-    // Calculate the allocation
-    let reward_percentage = fund.reward_percentage;
-    let reward_amount = rewards * reward_percentage / 100;
-    let remaining_rewards = rewards - reward_amount;
-
-    // Transfer the allocated rewards to the reward destination
-    let cpi_accounts = Transfer {
-        from: fund_token_account.to_account_info(),
-        to: reward_destination.to_account_info(),
-        authority: owner.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    token::transfer(cpi_ctx, reward_amount)?;
-
-    // Calculate and transfer the liquidity pool reward percentage
-    let liquidity_reward_amount = rewards * allocation.liquidity_pool_reward_percentage as u64 / 100;
-    let liquidity_reward_destination = Account::<TokenAccount>::try_from(&allocation.liquidity_pool_reward_destination)?;
-    let cpi_accounts_liquidity = Transfer {
-        from: fund_token_account.to_account_info(),
-        to: liquidity_reward_destination.to_account_info(),
-        authority: owner.to_account_info(),
-    };
-    let cpi_ctx_liquidity = CpiContext::new(cpi_program, cpi_accounts_liquidity);
-    token::transfer(cpi_ctx_liquidity, liquidity_reward_amount)?;
-
-    // Transfer the remaining rewards back to the fund
-    let cpi_accounts = Transfer {
-        from: fund_token_account.to_account_info(),
-        to: fund_token_account.to_account_info(),
-        authority: owner.to_account_info(),
-    };
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    token::transfer(cpi_ctx, remaining_rewards)?;
+#[derive(Accounts)]
+pub struct UpdateRewardRecipients<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+}
+
+pub fn set_reward_recipients(ctx: Context<UpdateRewardRecipients>, recipients: Vec<RewardRecipient>) -> ProgramResult {
+    if recipients.len() > MAX_REWARD_RECIPIENTS {
+        return Err(SaturnFundError::InvalidRewardSplit.into());
+    }
+
+    // Whatever the recipients do not claim is reinvested into the fund, so the shares may not exceed the whole
+    let mut total_bps: u64 = 0;
+    for recipient in recipients.iter() {
+        total_bps = total_bps
+            .checked_add(recipient.bps)
+            .ok_or::<ProgramError>(SaturnFundError::InvalidRewardSplit.into())?;
+    }
+    if total_bps > BPS_DENOMINATOR {
+        return Err(SaturnFundError::InvalidRewardSplit.into());
+    }
 
+    msg!("Reward split set: {} recipients take {} bps, fund keeps {} bps", recipients.len(), total_bps, BPS_DENOMINATOR - total_bps);
+    ctx.accounts.fund.reward_recipients = recipients;
     Ok(())
 }
 
-fn collect_rewards_from_raydium() -> Result<u64> {
-    // Placeholder logic to simulate reward collection
-    // Replace this with actual API calls or contract interactions with Raydium
-    Ok(1000)  // Simulating a reward collection of 1000 units
+pub fn set_compounding(ctx: Context<UpdateRewardRecipients>, enabled: bool) -> ProgramResult {
+    ctx.accounts.fund.compound_rewards = enabled;
+
+    msg!("Reward compounding {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
 }
 
-pub fn distribute_rewards(ctx: Context<CollectLiquidityPoolRewards>) -> ProgramResult {
-    let rewards = collect_rewards_from_raydium()?;
-    let allocation = &ctx.accounts.allocation;
+/// Sets how long compounded rewards take to unlock into NAV. Zero makes them count immediately.
+pub fn set_profit_vesting_period(ctx: Context<UpdateRewardRecipients>, vesting_period: i64) -> ProgramResult {
+    if vesting_period < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    let reward_percentage = allocation.liquidity_pool_reward_percentage as u64;
-    let reward_amount = rewards * reward_percentage / 100;
+    ctx.accounts.fund.profit_vesting_period = vesting_period;
 
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.fund_token_account.to_account_info(),
-        to: ctx.accounts.reward_destination.to_account_info(),
-        authority: ctx.accounts.owner.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    token::transfer(cpi_ctx, reward_amount)?;
+    msg!("Profit vesting period set to {} seconds", vesting_period);
+    Ok(())
+}
+
+/// Harvests the farm of the pool at `pool_index` and splits the rewards across the configured recipients.
+/// The fund's share stays in the reward vault, or is compounded into a holding when enabled,
+/// and vests into NAV either way.
+pub fn collect_liquidity_pool_rewards(mut ctx: Context<CollectLiquidityPoolRewards>, pool_index: u8) -> ProgramResult {
+    check_configured_farm(&ctx.accounts.allocation_pda, &ctx.accounts.farm, pool_index)?;
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    // Only what actually arrived in the reward vault is split
+    let rewards = harvest_farm_rewards(&ctx.accounts.farm, &[&seeds[..]])?;
+    let reinvested = pay_reward_recipients(
+        &ctx.accounts.fund,
+        &ctx.accounts.fund_token_account.to_account_info(),
+        &ctx.accounts.fund_authority,
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.token_accounts,
+        rewards,
+        &[&seeds[..]],
+    )?;
+
+    msg!("Rewards harvested: {}, reinvested into the fund: {}", rewards, reinvested);
+
+    let compounded = ctx.accounts.fund.compound_rewards && reinvested > 0 && compound_rewards(&mut ctx, reinvested)?;
+    if !compounded {
+        let now = ctx.accounts.clock.unix_timestamp;
+        let reward_vault = ctx.accounts.fund_token_account.key();
+        lock_retained_rewards(
+            &mut ctx.accounts.fund,
+            &ctx.accounts.allocation_pda,
+            &reward_vault,
+            &ctx.accounts.reward_price_oracle,
+            reinvested,
+            now,
+        )?;
+    }
 
     Ok(())
 }
 
-pub fn dynamic_liquidity_reward_calculation(ctx: Context<CollectLiquidityPoolRewards>) -> ProgramResult {
-    let rewards = collect_rewards_from_raydium()?;
-    let allocation = &ctx.accounts.allocation;
+/// Pays each recipient its share of `rewards` from the fund's reward vault and returns
+/// the fund's share, which stays in the vault
+pub fn pay_reward_recipients<'info>(
+    fund: &Fund,
+    reward_vault: &AccountInfo<'info>,
+    fund_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    rewards: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64, ProgramError> {
+    let (payouts, reinvested) = split_rewards(rewards, &fund.reward_recipients)?;
 
-    let reward_percentage = allocation.liquidity_pool_reward_percentage as u64;
-    let reward_amount = rewards * reward_percentage / 100;
+    for (destination, amount) in payouts {
+        if amount == 0 {
+            continue;
+        }
 
-    // Transfer the dynamically calculated reward amount
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.fund_token_account.to_account_info(),
-        to: ctx.accounts.reward_destination.to_account_info(),
-        authority: ctx.accounts.owner.to_account_info(),
+        let destination = find_account_by_key(accounts, &destination)?;
+        let cpi_accounts = Transfer {
+            from: reward_vault.clone(),
+            to: destination.clone(),
+            authority: fund_authority.clone(),
+        };
+        token::transfer(CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds), amount)?;
+    }
+
+    Ok(reinvested)
+}
+
+/// Locks the value of rewards the fund keeps when its reward vault is a holding vault counted
+/// in NAV, so the harvest vests in instead of raising the share price at once
+pub fn lock_retained_rewards(
+    fund: &mut Fund,
+    allocation: &Allocation,
+    reward_vault: &Pubkey,
+    reward_price_oracle: &AccountInfo,
+    amount: u64,
+    now: i64,
+) -> ProgramResult {
+    let holding = match allocation.holding_tokens.iter().find(|holding| holding.vault == *reward_vault) {
+        Some(holding) => holding,
+        None => return Ok(()),
     };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    token::transfer(cpi_ctx, reward_amount)?;
+    if amount == 0 {
+        return Ok(());
+    }
+    if *reward_price_oracle.key != holding.price_oracle {
+        return Err(ProgramError::InvalidAccountData);
+    }
 
+    let value = amount
+        .checked_mul(get_current_market_price_from_raydium(fund, reward_price_oracle)?)
+        .ok_or(ProgramError::InvalidArgument)?;
+    fund.lock_profit(value, now);
+
+    msg!("Locked {} of harvested profit, vesting over {} seconds", value, fund.profit_vesting_period);
     Ok(())
 }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Unauthorized")]
-    Unauthorized,
-}
\ No newline at end of file
+/// Swaps the reinvested rewards into the holding furthest below its target weight.
+/// Nothing is swapped when every holding is at or above target. The value added to the
+/// holding is locked and vests into NAV, so a harvest cannot be sniped by minting ahead of it.
+/// Returns whether the rewards were swapped.
+fn compound_rewards(ctx: &mut Context<CollectLiquidityPoolRewards>, amount: u64) -> Result<bool, ProgramError> {
+    // Only the holdings' weights relative to each other decide where rewards go
+    let plan = build_rebalance_plan(&ctx.accounts.fund, &ctx.accounts.allocation_pda, 0, &ctx.accounts.token_accounts)?;
+    let target = plan.iter()
+        .filter(|trade| trade.side == TradeSide::Buy)
+        .max_by_key(|trade| trade.amount as u128 * trade.expected_price as u128);
+    let target = match target {
+        Some(trade) => trade,
+        None => {
+            msg!("No under-weight holding, rewards left in the reward vault");
+            return Ok(false);
+        }
+    };
+
+    let reward_mint = ctx.accounts.fund_token_account.mint;
+    if target.mint == reward_mint {
+        return Ok(false);
+    }
+
+    let holding = ctx.accounts.allocation_pda.holding_tokens.iter()
+        .find(|holding| holding.token_mint == target.mint)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let holding_vault = find_account_by_key(&ctx.accounts.token_accounts, &holding.vault)?.clone();
+
+    let reward_price = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.reward_price_oracle)?;
+    let minimum_amount_out = min_amount_out(&ctx.accounts.fund, amount, reward_price, target.expected_price)?;
+
+    // Compounding trades count against the notional caps like any other trade
+    let notional = amount
+        .checked_mul(reward_price)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let now = ctx.accounts.clock.unix_timestamp;
+    record_trade_notional(&mut ctx.accounts.fund, notional, now)?;
+
+    let leg = SwapLeg {
+        input_mint: reward_mint,
+        output_mint: target.mint,
+        source: ctx.accounts.fund_token_account.to_account_info(),
+        destination: holding_vault,
+        authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    };
+    let venues = parse_venues(ctx.program_id, &ctx.accounts.fund.key(), &ctx.accounts.token_accounts)?;
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    msg!("Compounding {} reward tokens into {}", amount, target.mint);
+    let balance_before = token_account_amount(&leg.destination)?;
+    route_swap(&venues, &leg, amount, minimum_amount_out, &[&seeds[..]])?;
+    let balance_after = token_account_amount(&leg.destination)?;
+
+    let value_added = balance_after
+        .saturating_sub(balance_before)
+        .checked_mul(target.expected_price)
+        .ok_or(ProgramError::InvalidArgument)?;
+    ctx.accounts.fund.lock_profit(value_added, now);
+
+    msg!("Locked {} of profit, vesting over {} seconds", value_added, ctx.accounts.fund.profit_vesting_period);
+    Ok(true)
+}
+
+/// Splits `rewards` by each recipient's bps, rounding down. Returns the payouts and the fund's
+/// share, which takes the remainder so that the parts always sum to `rewards`.
+pub fn split_rewards(rewards: u64, recipients: &[RewardRecipient]) -> Result<(Vec<(Pubkey, u64)>, u64), ProgramError> {
+    let mut payouts = Vec::with_capacity(recipients.len());
+    let mut paid_out: u64 = 0;
+
+    for recipient in recipients.iter() {
+        let amount = (rewards as u128 * recipient.bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        paid_out = paid_out
+            .checked_add(amount)
+            .ok_or::<ProgramError>(SaturnFundError::InvalidRewardSplit.into())?;
+        payouts.push((recipient.destination, amount));
+    }
+
+    let reinvested = rewards
+        .checked_sub(paid_out)
+        .ok_or::<ProgramError>(SaturnFundError::InvalidRewardSplit.into())?;
+
+    Ok((payouts, reinvested))
+}
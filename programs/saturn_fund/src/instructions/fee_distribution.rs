@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, TokenAccount, Transfer};
+use crate::error::SaturnFundError;
+use crate::instructions::manage_holdings::find_account_by_key;
+use crate::state::fee_totals::{FeeTotals, FEE_TOTALS_SEED};
+use crate::state::fund::{FeeRecipient, FeeRecipientKind, Fund, FUND_AUTHORITY_SEED};
+use crate::BPS_DENOMINATOR;
+
+/// Seed prefix of the fund's fee vault, combined with the fund address
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+/// Upper bound on configured fee recipients, keeping distribution within compute limits
+pub const MAX_FEE_RECIPIENTS: usize = 8;
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct CreateFeeVault<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        seeds = [FEE_VAULT_SEED, fund.key().as_ref()],
+        bump = bump,
+        payer = owner,
+        token::mint = fee_mint,
+        token::authority = fund_authority,
+    )]
+    pub fee_vault: Account<'info, TokenAccount>,
+    pub fee_mint: Account<'info, Mint>, // Base currency mint and redeem fees are paid in
+    pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Creates the program-owned vault that collects the fund's mint and redeem fees
+pub fn create_fee_vault(ctx: Context<CreateFeeVault>, _bump: u8) -> ProgramResult {
+    ctx.accounts.fund.fee_vault = ctx.accounts.fee_vault.key();
+
+    msg!("Fee vault {} created", ctx.accounts.fee_vault.key());
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FeeRecipientConfig {
+    pub kind: FeeRecipientKind,
+    pub destination: Pubkey, // Token account of the fee mint
+    pub bps: u64,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeRecipients<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct CreateFeeTotals<'info> {
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub destination: AccountInfo<'info>, // Token account of the fee mint the totals are kept for
+    #[account(
+        init,
+        seeds = [FEE_TOTALS_SEED, fund.key().as_ref(), destination.key().as_ref()],
+        bump = bump,
+        payer = payer,
+        space = 8 + FeeTotals::LEN,
+    )]
+    pub fee_totals: Account<'info, FeeTotals>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates the lifetime fee record of a destination. Every destination in the split needs one
+/// before fees can be distributed to it.
+pub fn create_fee_totals(ctx: Context<CreateFeeTotals>, bump: u8) -> ProgramResult {
+    let totals = &mut ctx.accounts.fee_totals;
+    totals.fund = ctx.accounts.fund.key();
+    totals.destination = ctx.accounts.destination.key();
+    totals.bump = bump;
+
+    msg!("Fee totals created for {}", totals.destination);
+    Ok(())
+}
+
+/// Replaces the fee split. Shares must add up to the whole. Lifetime totals are kept per
+/// destination in `FeeTotals` accounts, so they are unaffected by changes to the split.
+pub fn set_fee_recipients(ctx: Context<UpdateFeeRecipients>, recipients: Vec<FeeRecipientConfig>) -> ProgramResult {
+    if recipients.is_empty() || recipients.len() > MAX_FEE_RECIPIENTS {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+
+    let mut total_bps: u64 = 0;
+    for recipient in recipients.iter() {
+        total_bps = total_bps
+            .checked_add(recipient.bps)
+            .ok_or::<ProgramError>(SaturnFundError::InvalidFee.into())?;
+    }
+    if total_bps != BPS_DENOMINATOR {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+
+    let fund = &mut ctx.accounts.fund;
+    fund.fee_recipients = recipients.into_iter()
+        .map(|recipient| FeeRecipient {
+            kind: recipient.kind,
+            destination: recipient.destination,
+            bps: recipient.bps,
+        })
+        .collect();
+
+    msg!("Fee split set across {} recipients", fund.fee_recipients.len());
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DistributeFees<'info> {
+    #[account(mut)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut, constraint = fee_vault.key() == fund.fee_vault)]
+    pub fee_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Token accounts of the configured fee recipients and their fee totals
+}
+
+/// Adds `amount` to the lifetime total of `destination`, whose totals account is passed among `accounts`
+fn credit_fee_totals<'info>(
+    program_id: &Pubkey,
+    fund_key: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+    destination: &Pubkey,
+    amount: u64,
+) -> ProgramResult {
+    let totals_key = Pubkey::find_program_address(
+        &[FEE_TOTALS_SEED, fund_key.as_ref(), destination.as_ref()],
+        program_id,
+    ).0;
+    let totals_info = find_account_by_key(accounts, &totals_key)?;
+    let mut totals = Account::<FeeTotals>::try_from(totals_info)?;
+    if totals.fund != *fund_key || totals.destination != *destination {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    totals.lifetime_fees = totals.lifetime_fees
+        .checked_add(amount)
+        .ok_or(ProgramError::InvalidArgument)?;
+    totals.exit(program_id)
+}
+
+/// Permissionless crank paying out the fee vault by the configured split. Unclaimed referral
+/// credits are held back, and rounding dust stays in the vault for the next distribution.
+pub fn distribute_fees(ctx: Context<DistributeFees>) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    let distributable = ctx.accounts.fee_vault.amount.saturating_sub(ctx.accounts.fund.referral_fees_owed);
+
+    if ctx.accounts.fund.fee_recipients.is_empty() {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+
+    for index in 0..ctx.accounts.fund.fee_recipients.len() {
+        let recipient = &ctx.accounts.fund.fee_recipients[index];
+        let amount = (distributable as u128 * recipient.bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        if amount == 0 {
+            continue;
+        }
+
+        let destination = find_account_by_key(&ctx.accounts.token_accounts, &recipient.destination)?;
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.fee_vault.to_account_info(),
+            to: destination.clone(),
+            authority: ctx.accounts.fund_authority.clone(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new_with_signer(cpi_program, cpi_accounts, &[&seeds[..]]), amount)?;
+
+        credit_fee_totals(ctx.program_id, &fund_key, &ctx.accounts.token_accounts, &recipient.destination, amount)?;
+        msg!("Paid {} in fees to {:?} {}", amount, recipient.kind, recipient.destination);
+    }
+
+    Ok(())
+}
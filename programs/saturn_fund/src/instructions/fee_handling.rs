@@ -1,40 +1,464 @@
 // In fee_handling.rs
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, TokenAccount, Transfer};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::{Account as TokenAccount2022, Mint as Mint2022};
+use crate::error::SaturnFundError;
+use crate::instructions::calculate_price_of_fund::get_fund_value;
+use crate::instructions::manage_holdings::find_account_by_key;
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, MintFeeTier, RedeemFeeTier, FUND_AUTHORITY_SEED};
+use crate::state::position::{UserPosition, USER_POSITION_SEED};
+use crate::BPS_DENOMINATOR;
 
-pub fn transfer_with_fee(ctx: Context<TransferWithFee>, amount: u64, fee: u64) -> ProgramResult {
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+/// Highest annual management fee the owner can configure
+pub const MAX_MANAGEMENT_FEE_BPS: u64 = 500;
+/// Highest performance fee the owner can configure
+pub const MAX_PERFORMANCE_FEE_BPS: u64 = 3_000;
+/// Fixed-point scale of NAV per share, matching the $STRN mint's 9 decimals
+pub const SHARE_PRICE_SCALE: u128 = 1_000_000_000;
+/// Allowed (min, max) range of each entry/exit fee, in bps
+pub const MINT_FEE_BPS_BOUNDS: (u64, u64) = (0, 200);
+pub const REDEEM_FEE_BPS_BOUNDS: (u64, u64) = (0, 200);
+pub const TRANSFER_FEE_BPS_BOUNDS: (u64, u64) = (0, 100);
+pub const REFERRAL_FEE_BPS_BOUNDS: (u64, u64) = (0, 5_000);
+/// Upper bound on the entries of each fee schedule
+pub const MAX_FEE_TIERS: usize = 8;
+
+/// Fee owed on `amount` at `fee_bps`, rounded up so the fund never undercharges
+pub fn fee_amount(amount: u64, fee_bps: u64) -> Result<u64, ProgramError> {
+    let fee = (amount as u128 * fee_bps as u128 + BPS_DENOMINATOR as u128 - 1) / BPS_DENOMINATOR as u128;
+    u64::try_from(fee).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Mint fee for a deposit of `amount`: the largest size tier it reaches, or the base mint fee
+pub fn mint_fee_bps_for(fund: &Fund, amount: u64) -> u64 {
+    fund.mint_fee_tiers.iter()
+        .rev()
+        .find(|tier| amount >= tier.min_amount)
+        .map_or(fund.mint_fee_bps, |tier| tier.fee_bps)
+}
+
+/// Redeem fee for shares held `holding_period` seconds: the longest period tier reached, or the base redeem fee
+pub fn redeem_fee_bps_for(fund: &Fund, holding_period: i64) -> u64 {
+    fund.redeem_fee_schedule.iter()
+        .rev()
+        .find(|tier| holding_period >= tier.min_holding_seconds)
+        .map_or(fund.redeem_fee_bps, |tier| tier.fee_bps)
+}
+
+/// Redeem fee on `value` paid out for `shares`. Shares the position holds pay the holding-period
+/// rate; any beyond that arrived by transfer and pay the base redeem fee.
+pub fn redeem_fee_for(fund: &Fund, position: &UserPosition, shares: u64, value: u64, now: i64) -> Result<u64, ProgramError> {
+    if shares == 0 {
+        return Ok(0);
+    }
+
+    let covered_shares = shares.min(position.shares);
+    let covered_value = (value as u128 * covered_shares as u128 / shares as u128) as u64;
+    let scheduled_fee = fee_amount(covered_value, redeem_fee_bps_for(fund, position.holding_period(now)))?;
+    let base_fee = fee_amount(value - covered_value, fund.redeem_fee_bps)?;
+
+    scheduled_fee.checked_add(base_fee).ok_or(ProgramError::InvalidArgument)
+}
+
+/// Splits `amount` into what remains after the fee, and the fee itself
+pub fn apply_fee(amount: u64, fee_bps: u64) -> Result<(u64, u64), ProgramError> {
+    let fee = fee_amount(amount, fee_bps)?;
     let amount_after_fee = amount.checked_sub(fee).ok_or(ProgramError::InsufficientFunds)?;
-    token::transfer(ctx.accounts.transfer_context(), amount_after_fee)?;
-    token::transfer(ctx.accounts.fee_context(), fee)?;
+    Ok((amount_after_fee, fee))
+}
+
+/// Whether `key` is a token program a share mint can live under
+pub fn is_token_program(key: &Pubkey) -> bool {
+    *key == token::ID || *key == spl_token_2022::ID
+}
+
+/// Checks that `account` is a token account of the fund's share mint under the program owning the mint,
+/// returning the wallet that owns it
+pub fn check_share_account(fund: &Fund, share_token_program: &Pubkey, account: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    if account.owner != share_token_program {
+        return Err(ProgramError::IllegalOwner);
+    }
+    let data = account.try_borrow_data()?;
+    let state = StateWithExtensions::<TokenAccount2022>::unpack(&data)?;
+    if state.base.mint != fund.share_mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(state.base.owner)
+}
+
+/// Supply of a share mint owned by either token program
+pub fn share_supply(mint: &AccountInfo) -> Result<u64, ProgramError> {
+    if !is_token_program(mint.owner) {
+        return Err(ProgramError::IllegalOwner);
+    }
+    let data = mint.try_borrow_data()?;
+    Ok(StateWithExtensions::<Mint2022>::unpack(&data)?.base.supply)
+}
+
+/// Accounts needed to pay fees by minting $STRN, shared by every instruction that accrues fees.
+/// Works with share mints owned by either the SPL Token or the Token-2022 program.
+pub struct ShareMint<'info> {
+    pub mint: AccountInfo<'info>,
+    pub fee_recipient: AccountInfo<'info>,
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> ShareMint<'info> {
+    fn supply(&self) -> Result<u64, ProgramError> {
+        share_supply(&self.mint)
+    }
+
+    fn mint_fee(&self, fund: &Account<'info, Fund>, amount: u64) -> ProgramResult {
+        let fund_key = fund.key();
+        let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[fund.authority_bump]];
+
+        let ix = spl_token_2022::instruction::mint_to(
+            self.token_program.key,
+            self.mint.key,
+            self.fee_recipient.key,
+            self.fund_authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[
+                self.mint.clone(),
+                self.fee_recipient.clone(),
+                self.fund_authority.clone(),
+                self.token_program.clone(),
+            ],
+            &[&seeds[..]],
+        )
+    }
+}
+
+/// Accrues the management fee for the time elapsed since the last accrual by minting $STRN
+/// to the fee recipient. The shares minted dilute holders by exactly the fee fraction:
+/// `supply * f / (1 - f)` with `f = fee_bps * elapsed / (BPS_DENOMINATOR * SECONDS_PER_YEAR)`.
+/// Gaps longer than a year accrue one year at a time, each on the supply left by the last.
+pub fn accrue_management_fee<'info>(fund: &mut Account<'info, Fund>, share_mint: &ShareMint<'info>, now: i64) -> ProgramResult {
+    let elapsed = now.saturating_sub(fund.last_fee_accrual);
+    if fund.last_fee_accrual == 0 || fund.management_fee_bps == 0 {
+        fund.last_fee_accrual = now;
+        return Ok(());
+    }
+    if elapsed <= 0 {
+        return Ok(());
+    }
+
+    let supply = share_mint.supply()?;
+    let mut diluted_supply = supply as u128;
+    let mut remaining = elapsed;
+    while remaining > 0 {
+        let period = remaining.min(SECONDS_PER_YEAR);
+        let fee_numerator = fund.management_fee_bps as u128 * period as u128;
+        let fee_denominator = (BPS_DENOMINATOR as u128 * SECONDS_PER_YEAR as u128)
+            .checked_sub(fee_numerator)
+            .filter(|denominator| *denominator > 0)
+            .ok_or(ProgramError::InvalidArgument)?;
+        diluted_supply = diluted_supply
+            .checked_add(diluted_supply * fee_numerator / fee_denominator)
+            .ok_or(ProgramError::InvalidArgument)?;
+        remaining -= period;
+    }
+    let fee_shares = u64::try_from(diluted_supply - supply as u128)
+        .map_err(|_| ProgramError::InvalidArgument)?;
+
+    // Leave the accrual clock running while the fee rounds to zero so frequent calls can't skip it
+    if fee_shares == 0 && supply > 0 {
+        return Ok(());
+    }
+
+    if fee_shares > 0 {
+        if *share_mint.mint.key != fund.share_mint || *share_mint.fee_recipient.key != fund.fee_recipient {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        share_mint.mint_fee(fund, fee_shares)?;
+        msg!("Management fee accrued: {} shares for {} seconds", fee_shares, elapsed);
+    }
+    fund.last_fee_accrual = now;
+    Ok(())
+}
+
+/// Charges the performance fee on NAV-per-share gains above the high-water mark, at most once per
+/// crystallization interval. The fee is paid in shares worth `performance_fee_bps` of the gain, and the
+/// high-water mark moves to the NAV per share left after the fee.
+pub fn crystallize_performance_fee<'info>(
+    fund: &mut Account<'info, Fund>,
+    share_mint: &ShareMint<'info>,
+    fund_value: u64,
+    now: i64,
+) -> ProgramResult {
+    if fund.performance_fee_bps == 0 || now < fund.last_crystallization.saturating_add(fund.crystallization_interval) {
+        return Ok(());
+    }
+
+    let supply = share_mint.supply()?;
+    if supply == 0 {
+        return Ok(());
+    }
+
+    let nav_per_share = fund_value as u128 * SHARE_PRICE_SCALE / supply as u128;
+    fund.last_crystallization = now;
+
+    // The first crystallization only sets the mark
+    if fund.high_water_mark == 0 || nav_per_share <= fund.high_water_mark as u128 {
+        if fund.high_water_mark == 0 {
+            fund.high_water_mark = u64::try_from(nav_per_share).map_err(|_| ProgramError::InvalidArgument)?;
+        }
+        return Ok(());
+    }
+
+    let gain = (nav_per_share - fund.high_water_mark as u128) * supply as u128 / SHARE_PRICE_SCALE;
+    let fee_value = gain * fund.performance_fee_bps as u128 / BPS_DENOMINATOR as u128;
+    let remaining_value = (fund_value as u128)
+        .checked_sub(fee_value)
+        .filter(|value| *value > 0)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let fee_shares = u64::try_from(supply as u128 * fee_value / remaining_value)
+        .map_err(|_| ProgramError::InvalidArgument)?;
+
+    if fee_shares > 0 {
+        if *share_mint.mint.key != fund.share_mint || *share_mint.fee_recipient.key != fund.fee_recipient {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        share_mint.mint_fee(fund, fee_shares)?;
+    }
+
+    let new_supply = supply as u128 + fee_shares as u128;
+    fund.high_water_mark = u64::try_from(fund_value as u128 * SHARE_PRICE_SCALE / new_supply)
+        .map_err(|_| ProgramError::InvalidArgument)?;
+
+    msg!("Performance fee crystallized: {} shares, high-water mark {}", fee_shares, fund.high_water_mark);
     Ok(())
 }
 
 #[derive(Accounts)]
-pub struct TransferWithFee<'info> {
-    #[account(mut)]
-    pub from: Account<'info, TokenAccount>,
+pub struct AccrueFees<'info> {
     #[account(mut)]
-    pub to: Account<'info, TokenAccount>,
+    pub fund: Account<'info, Fund>,
+    #[account(mut, constraint = mint.key() == fund.share_mint && *mint.owner == token_program.key())]
+    pub mint: AccountInfo<'info>,
+    #[account(mut, constraint = fee_recipient.key() == fund.fee_recipient)]
+    pub fee_recipient: AccountInfo<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(constraint = is_token_program(&token_program.key()))]
+    pub token_program: AccountInfo<'info>, // Program owning the share mint
+    pub clock: Sysvar<'info, Clock>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults, LP vaults, pool vaults and oracles, to value the fund
+}
+
+impl<'info> AccrueFees<'info> {
+    pub fn share_mint(&self) -> ShareMint<'info> {
+        ShareMint {
+            mint: self.mint.clone(),
+            fee_recipient: self.fee_recipient.clone(),
+            fund_authority: self.fund_authority.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+}
+
+/// Permissionless crank bringing fee accrual up to date. Management fees accrue first so the
+/// performance fee is measured on the diluted NAV per share.
+pub fn accrue_fees(ctx: Context<AccrueFees>) -> ProgramResult {
+    let share_mint = ctx.accounts.share_mint();
+    let now = ctx.accounts.clock.unix_timestamp;
+
+    accrue_management_fee(&mut ctx.accounts.fund, &share_mint, now)?;
+
+    let fund_value = get_fund_value(&ctx.accounts.fund, &ctx.accounts.allocation_pda, &ctx.accounts.token_accounts, now)?;
+    crystallize_performance_fee(&mut ctx.accounts.fund, &share_mint, fund_value, now)
+}
+
+#[derive(Accounts)]
+pub struct UpdateFees<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(mut, constraint = mint.key() == fund.share_mint && *mint.owner == token_program.key())]
+    pub mint: AccountInfo<'info>,
     #[account(mut)]
-    pub fee_account: Account<'info, TokenAccount>,
-    pub authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub fee_recipient: AccountInfo<'info>, // Current recipient, paid the fees accrued so far
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(constraint = is_token_program(&token_program.key()))]
+    pub token_program: AccountInfo<'info>, // Program owning the share mint
+    pub clock: Sysvar<'info, Clock>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults, LP vaults, pool vaults and oracles, plus the new recipient's account
 }
 
-impl<'info> TransferWithFee<'info> {
-    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        CpiContext::new(self.token_program.to_account_info(), Transfer {
-            from: self.from.to_account_info(),
-            to: self.to.to_account_info(),
-            authority: self.authority.to_account_info(),
-        })
+impl<'info> UpdateFees<'info> {
+    pub fn share_mint(&self) -> ShareMint<'info> {
+        ShareMint {
+            mint: self.mint.clone(),
+            fee_recipient: self.fee_recipient.clone(),
+            fund_authority: self.fund_authority.clone(),
+            token_program: self.token_program.clone(),
+        }
     }
 
-    fn fee_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        CpiContext::new(self.token_program.to_account_info(), Transfer {
-            from: self.from.to_account_info(),
-            to: self.fee_account.to_account_info(),
-            authority: self.authority.to_account_info(),
-        })
+    /// Settles both fees at the current rates, so a rate change only applies from now on
+    fn settle_fees(&mut self) -> ProgramResult {
+        let share_mint = self.share_mint();
+        let now = self.clock.unix_timestamp;
+
+        accrue_management_fee(&mut self.fund, &share_mint, now)?;
+
+        let fund_value = get_fund_value(&self.fund, &self.allocation_pda, &self.token_accounts, now)?;
+        crystallize_performance_fee(&mut self.fund, &share_mint, fund_value, now)
     }
 }
+
+
+/// Sets the management fee and its recipient. Fees are accrued and crystallized at the old rates first.
+/// The new recipient's token account is passed with the token accounts.
+pub fn set_management_fee(mut ctx: Context<UpdateFees>, management_fee_bps: u64, fee_recipient: Pubkey) -> ProgramResult {
+    if management_fee_bps > MAX_MANAGEMENT_FEE_BPS {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+    if fee_recipient != Pubkey::default() {
+        let recipient_account = find_account_by_key(&ctx.accounts.token_accounts, &fee_recipient)?;
+        check_share_account(&ctx.accounts.fund, ctx.accounts.token_program.key, recipient_account)?;
+    }
+
+    ctx.accounts.settle_fees()?;
+
+    let fund = &mut ctx.accounts.fund;
+    fund.management_fee_bps = management_fee_bps;
+    fund.fee_recipient = fee_recipient;
+
+    msg!("Management fee set to {} bps, paid to {}", management_fee_bps, fee_recipient);
+    Ok(())
+}
+
+/// Sets the performance fee and how often it may crystallize. Zero bps disables it.
+/// Fees are accrued and crystallized at the old rates first.
+pub fn set_performance_fee(mut ctx: Context<UpdateFees>, performance_fee_bps: u64, crystallization_interval: i64) -> ProgramResult {
+    if performance_fee_bps > MAX_PERFORMANCE_FEE_BPS || crystallization_interval <= 0 {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+
+    ctx.accounts.settle_fees()?;
+
+    let fund = &mut ctx.accounts.fund;
+    fund.performance_fee_bps = performance_fee_bps;
+    fund.crystallization_interval = crystallization_interval;
+
+    msg!("Performance fee set to {} bps, crystallized every {} seconds", performance_fee_bps, crystallization_interval);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeRates<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+}
+
+/// Sets the entry and exit fees, each within its configured bounds. The fee on secondary
+/// share transfers lives on the Token-2022 mint, see `mint_management::set_share_transfer_fee`.
+pub fn set_fee_rates(ctx: Context<UpdateFeeRates>, mint_fee_bps: u64, redeem_fee_bps: u64) -> ProgramResult {
+    check_fee_bounds(mint_fee_bps, MINT_FEE_BPS_BOUNDS)?;
+    check_fee_bounds(redeem_fee_bps, REDEEM_FEE_BPS_BOUNDS)?;
+
+    let fund = &mut ctx.accounts.fund;
+    fund.mint_fee_bps = mint_fee_bps;
+    fund.redeem_fee_bps = redeem_fee_bps;
+
+    msg!("Fees set: mint {} bps, redeem {} bps", mint_fee_bps, redeem_fee_bps);
+    Ok(())
+}
+
+/// Sets the share of each mint fee credited to the minter's referrer
+pub fn set_referral_fee(ctx: Context<UpdateFeeRates>, referral_fee_bps: u64) -> ProgramResult {
+    check_fee_bounds(referral_fee_bps, REFERRAL_FEE_BPS_BOUNDS)?;
+    ctx.accounts.fund.referral_fee_bps = referral_fee_bps;
+
+    msg!("Referral share of the mint fee set to {} bps", referral_fee_bps);
+    Ok(())
+}
+
+pub fn check_fee_bounds(fee_bps: u64, (min_bps, max_bps): (u64, u64)) -> ProgramResult {
+    if fee_bps < min_bps || fee_bps > max_bps {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+    Ok(())
+}
+
+/// Replaces the mint fee size tiers and the redeem fee holding-period schedule. Both must be in
+/// ascending order and every fee must lie within the bounds of its base fee.
+pub fn set_fee_schedule(
+    ctx: Context<UpdateFeeRates>,
+    mint_fee_tiers: Vec<MintFeeTier>,
+    redeem_fee_schedule: Vec<RedeemFeeTier>,
+) -> ProgramResult {
+    if mint_fee_tiers.len() > MAX_FEE_TIERS || redeem_fee_schedule.len() > MAX_FEE_TIERS {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+    for tier in mint_fee_tiers.iter() {
+        check_fee_bounds(tier.fee_bps, MINT_FEE_BPS_BOUNDS)?;
+    }
+    for tier in redeem_fee_schedule.iter() {
+        check_fee_bounds(tier.fee_bps, REDEEM_FEE_BPS_BOUNDS)?;
+    }
+    if mint_fee_tiers.windows(2).any(|pair| pair[0].min_amount >= pair[1].min_amount)
+        || redeem_fee_schedule.windows(2).any(|pair| pair[0].min_holding_seconds >= pair[1].min_holding_seconds)
+    {
+        return Err(SaturnFundError::InvalidFee.into());
+    }
+
+    let fund = &mut ctx.accounts.fund;
+    fund.mint_fee_tiers = mint_fee_tiers;
+    fund.redeem_fee_schedule = redeem_fee_schedule;
+
+    msg!("Fee schedule set: {} mint tiers, {} redeem tiers", fund.mint_fee_tiers.len(), fund.redeem_fee_schedule.len());
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct OpenUserPosition<'info> {
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init,
+        seeds = [USER_POSITION_SEED, fund.key().as_ref(), user.key().as_ref()],
+        bump = bump,
+        payer = user,
+        space = 8 + UserPosition::LEN,
+    )]
+    pub user_position: Account<'info, UserPosition>,
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates the position PDA that tracks a holder's deposit time for the redeem fee schedule
+pub fn open_user_position(ctx: Context<OpenUserPosition>, bump: u8) -> ProgramResult {
+    let position = &mut ctx.accounts.user_position;
+    position.fund = ctx.accounts.fund.key();
+    position.owner = ctx.accounts.user.key();
+    position.shares = 0;
+    position.deposit_timestamp = ctx.accounts.clock.unix_timestamp; // Nothing held yet counts as held from now
+    position.bump = bump;
+    Ok(())
+}
@@ -1,5 +1,58 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, TokenAccount, Transfer};
+use anchor_lang::solana_program::program::invoke;
+use anchor_spl::token::{self, CloseAccount, TokenAccount};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use crate::error::SaturnFundError;
+use crate::instructions::allowlist::is_allowlisted;
+use crate::instructions::manage_holdings::find_account_by_key;
+use crate::instructions::trade_limits::{
+    DEFAULT_EPOCH_DURATION, DEFAULT_MAX_EPOCH_NOTIONAL, DEFAULT_MAX_ORACLE_AGE, DEFAULT_MAX_SLIPPAGE_BPS,
+    DEFAULT_MAX_TRADE_NOTIONAL,
+};
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+
+#[derive(Accounts)]
+#[instruction(authority_bump: u8)]
+pub struct InitializeFund<'info> {
+    #[account(init, payer = owner, space = 8 + Fund::LEN)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates a fund owned by the signer. The account is sized for every list at its cap,
+/// the owner starts out as keeper and compliance authority, and trading starts with the
+/// default limits so trades are not rejected before the owner tunes them.
+pub fn initialize_fund(ctx: Context<InitializeFund>, authority_bump: u8, oracle_program: Pubkey) -> ProgramResult {
+    let now = ctx.accounts.clock.unix_timestamp;
+    let owner = ctx.accounts.owner.key();
+
+    let fund = &mut ctx.accounts.fund;
+    fund.owner = owner;
+    fund.authority_bump = authority_bump;
+    fund.keeper = owner;
+    fund.compliance_authority = owner;
+    fund.last_fee_accrual = now;
+    fund.last_crystallization = now;
+    fund.epoch_start = now;
+    fund.max_slippage_bps = DEFAULT_MAX_SLIPPAGE_BPS;
+    fund.max_trade_notional = DEFAULT_MAX_TRADE_NOTIONAL;
+    fund.max_epoch_notional = DEFAULT_MAX_EPOCH_NOTIONAL;
+    fund.epoch_duration = DEFAULT_EPOCH_DURATION;
+    fund.oracle_program = oracle_program;
+    fund.max_oracle_age = DEFAULT_MAX_ORACLE_AGE;
+
+    msg!("Fund {} initialized", fund.key());
+    Ok(())
+}
 
 #[derive(Accounts)]
 pub struct FundAccountOperations<'info> {
@@ -9,19 +62,119 @@ pub struct FundAccountOperations<'info> {
     pub token_program: Program<'info, token::Token>,
 }
 
-pub fn create_fund_account(ctx: Context<FundAccountOperations>, owner: Pubkey) -> ProgramResult {
-    let fund_account = &mut ctx.accounts.fund_account;
-    fund_account.set_authority(owner)?;
+#[derive(Accounts)]
+pub struct CreateHoldingVaults<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>, // Pays the rent of new vaults
+    #[account(mut, has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+    #[account(address = spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    // Each holding's mint and vault are passed as remaining accounts
+}
+
+/// Creates the fund PDA's associated token account for every holding mint that lacks one, and
+/// records it as the holding's vault. Vaults that already exist are left untouched.
+pub fn create_holding_vaults(ctx: Context<CreateHoldingVaults>) -> ProgramResult {
+    let fund_authority = ctx.accounts.fund_authority.key();
+    let accounts = ctx.remaining_accounts;
+    let mut created = 0;
+
+    for holding in ctx.accounts.allocation_pda.holding_tokens.iter_mut() {
+        let vault_address = get_associated_token_address(&fund_authority, &holding.token_mint);
+        let vault = find_account_by_key(accounts, &vault_address)?;
+
+        if vault.data_is_empty() {
+            let mint = find_account_by_key(accounts, &holding.token_mint)?;
+            invoke(
+                &create_associated_token_account(ctx.accounts.owner.key, &fund_authority, &holding.token_mint),
+                &[
+                    ctx.accounts.owner.to_account_info(),
+                    vault.clone(),
+                    ctx.accounts.fund_authority.clone(),
+                    mint.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.rent.to_account_info(),
+                    ctx.accounts.associated_token_program.clone(),
+                ],
+            )?;
+            created += 1;
+        }
 
-    msg!("Fund account created with external owner.");
+        holding.vault = vault_address;
+    }
+
+    msg!("Created {} holding vaults", created);
     Ok(())
 }
 
-pub fn create_non_pda_fund_account(ctx: Context<FundAccountOperations>, owner: Pubkey) -> ProgramResult {
-    let fund_account = &mut ctx.accounts.fund_account;
-    fund_account.set_authority(owner)?;
+#[derive(Accounts)]
+pub struct CloseHoldingVault<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>, // Receives the reclaimed rent
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = vault.owner == fund_authority.key())]
+    pub vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, token::Token>,
+}
+
+/// Closes an empty vault of a holding no longer in the allocation and returns its rent to the owner.
+/// Only the fund PDA's associated token account of the removed mint qualifies, never a fee, LP or reward vault.
+pub fn close_holding_vault(ctx: Context<CloseHoldingVault>) -> ProgramResult {
+    let vault = &ctx.accounts.vault;
+    let allocation = &ctx.accounts.allocation_pda;
+    let vault_key = vault.key();
+    if vault_key != get_associated_token_address(&ctx.accounts.fund_authority.key(), &vault.mint) {
+        return Err(SaturnFundError::NotHoldingVault.into());
+    }
+    let reserved = vault_key == ctx.accounts.fund.fee_vault
+        || vault_key == allocation.liquidity_pool_reward_destination
+        || allocation.liquidity_pools.iter().any(|pool| pool.lp_vault == vault_key || pool.lp_mint == vault.mint);
+    if reserved {
+        return Err(SaturnFundError::NotHoldingVault.into());
+    }
+
+    let still_held = allocation.holding_tokens.iter()
+        .any(|holding| holding.token_mint == vault.mint || holding.vault == vault.key());
+    if still_held {
+        return Err(SaturnFundError::HoldingStillAllocated.into());
+    }
+    if vault.amount > 0 {
+        return Err(SaturnFundError::VaultNotEmpty.into());
+    }
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    token::close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: vault.to_account_info(),
+            destination: ctx.accounts.owner.to_account_info(),
+            authority: ctx.accounts.fund_authority.clone(),
+        },
+        &[&seeds[..]],
+    ))?;
 
-    msg!("Non-PDA Fund account created with external owner.");
+    msg!("Closed holding vault {}", vault.key());
     Ok(())
 }
 
@@ -35,9 +188,16 @@ pub fn manage_liquidity(ctx: Context<FundAccountOperations>, params: LiquidityPa
     Ok(())
 }
 
-pub fn enforce_transfer_restrictions(ctx: Context<FundAccountOperations>, amount: u64) -> ProgramResult {
-    // Ensure only the program can call this function
-    require!(ctx.accounts.owner.key() == ctx.program_id, ProgramError::IllegalOwner);
+/// In restricted mode, only users with an allowlist entry among `accounts` may mint or redeem
+pub fn enforce_transfer_restrictions<'info>(
+    program_id: &Pubkey,
+    fund: &Account<'info, Fund>,
+    accounts: &[AccountInfo<'info>],
+    user: &Pubkey,
+) -> ProgramResult {
+    if fund.restricted && !is_allowlisted(program_id, fund, accounts, user)? {
+        return Err(SaturnFundError::NotAllowlisted.into());
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -1,301 +1,401 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::set_return_data;
 use anchor_spl::token::{self, TokenAccount, Transfer};
+use crate::instructions::fee_handling::{accrue_management_fee, is_token_program, ShareMint};
+use crate::instructions::raydium_integration::get_current_market_price_from_raydium;
+use crate::instructions::swap_venue::{parse_venues, route_swap, SwapLeg, SwapVenue};
+use crate::instructions::trade_limits::{apply_slippage, record_trade_notional};
+use crate::state::allocation::{
+    Allocation, LiquidityPoolAllocation, TokenAllocation, ALLOCATION_SEED, MAX_HOLDINGS, MAX_LIQUIDITY_POOLS,
+};
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+use crate::BPS_DENOMINATOR;
 
 #[derive(Accounts)]
 pub struct ManageHoldings<'info> {
-    #[account(mut)]
-    pub fund_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = fund_account.owner == fund_authority.key())]
+    pub fund_account: Account<'info, TokenAccount>, // Base currency the holdings are traded against
     pub token_program: Program<'info, token::Token>,
-    pub token_accounts: Vec<Account<'info, TokenAccount>>, // All token accounts managed by the fund
-    // Add other token accounts representing different holdings
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(mut)]
+    pub fund: Account<'info, Fund>, // Holds the slippage and notional limits for trades
+    #[account(constraint = keeper.key() == fund.keeper || keeper.key() == fund.owner)]
+    pub keeper: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = share_mint.key() == fund.share_mint && *share_mint.owner == share_token_program.key())]
+    pub share_mint: AccountInfo<'info>, // $STRN mint, for accruing management fees
+    #[account(constraint = is_token_program(&share_token_program.key()))]
+    pub share_token_program: AccountInfo<'info>, // SPL Token or Token-2022, whichever owns the share mint
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults, price oracles and swap venue account groups
 }
 
-pub fn rebalance_holdings(ctx: Context<ManageHoldings>) -> ProgramResult {
-    // Logic to calculate current percentages of each holding
-    let current_percentages = calculate_current_percentages(&ctx)?;
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct CreateAllocation<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init,
+        seeds = [ALLOCATION_SEED, fund.key().as_ref()],
+        bump = bump,
+        payer = owner,
+        space = 8 + Allocation::LEN,
+    )]
+    pub allocation_pda: Account<'info, Allocation>,
+    pub system_program: Program<'info, System>,
+}
 
-    // Logic to adjust holdings to target percentages
-    adjust_to_target_percentages(&ctx, current_percentages)?;
+pub fn create_allocation(ctx: Context<CreateAllocation>, bump: u8) -> ProgramResult {
+    let allocation = &mut ctx.accounts.allocation_pda;
+    allocation.fund = ctx.accounts.fund.key();
+    allocation.bump = bump;
 
+    msg!("Allocation created for fund {}", allocation.fund);
     Ok(())
 }
 
-fn calculate_current_percentages(ctx: &Context<ManageHoldings>) -> Result<HashMap<Pubkey, f64>, ProgramError> {
-    let total_value = get_total_fund_value(ctx)?;
-    let mut percentages = HashMap::new();
+#[derive(Accounts)]
+pub struct UpdateAllocation<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [ALLOCATION_SEED, fund.key().as_ref()],
+        bump = allocation_pda.bump,
+    )]
+    pub allocation_pda: Account<'info, Allocation>,
+}
 
-    for token_account in &ctx.accounts.token_accounts {
-        let market_price = get_market_price(&token_account.mint)?;
-        let value = token_account.amount
-            .checked_mul(market_price)
-            .ok_or(ProgramError::Overflow)?;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AllocationConfig {
+    pub holding_tokens: Vec<TokenAllocation>,
+    pub target_amount_percentage: u8,
+    pub baseline_amount_percentage: u8,
+    pub liquidity_target_percentage: u8,
+    pub liquidity_band_percentage: u8,
+    pub liquidity_pools: Vec<LiquidityPoolAllocation>,
+}
 
-        let percentage = (value as f64 / total_value as f64) * 100.0;
-        percentages.insert(token_account.mint, percentage);
+/// Replaces the fund's target holdings and LP pools. Weights of each list may not exceed 100%.
+pub fn set_allocation(ctx: Context<UpdateAllocation>, config: AllocationConfig) -> ProgramResult {
+    let holdings_percentage: u64 = config.holding_tokens.iter().map(|holding| holding.percentage as u64).sum();
+    let pools_percentage: u64 = config.liquidity_pools.iter().map(|pool| pool.percentage as u64).sum();
+    if config.holding_tokens.len() > MAX_HOLDINGS
+        || config.liquidity_pools.len() > MAX_LIQUIDITY_POOLS
+        || holdings_percentage > 100
+        || pools_percentage > 100
+        || config.target_amount_percentage as u64 + config.baseline_amount_percentage as u64 > 100
+        || config.liquidity_target_percentage > 100
+    {
+        return Err(ProgramError::InvalidArgument);
     }
 
-    Ok(percentages)
+    let allocation = &mut ctx.accounts.allocation_pda;
+    allocation.holding_tokens = config.holding_tokens;
+    allocation.target_amount_percentage = config.target_amount_percentage;
+    allocation.baseline_amount_percentage = config.baseline_amount_percentage;
+    allocation.liquidity_target_percentage = config.liquidity_target_percentage;
+    allocation.liquidity_band_percentage = config.liquidity_band_percentage;
+    allocation.liquidity_pools = config.liquidity_pools;
+
+    msg!(
+        "Allocation set: {} holdings, {} liquidity pools",
+        allocation.holding_tokens.len(),
+        allocation.liquidity_pools.len()
+    );
+    Ok(())
 }
 
-fn adjust_to_target_percentages(
-    ctx: &Context<ManageHoldings>, 
-    target_percentages: HashMap<Pubkey, f64>
-) -> ProgramResult {
-    let total_fund_value = get_total_fund_value(ctx)?;
-    let current_percentages = calculate_current_percentages(ctx)?;
-
-    for (mint, target_percentage) in target_percentages {
-        let current_percentage = current_percentages.get(&mint).unwrap_or(&0.0);
-        let amount_to_adjust = calculate_amount_to_adjust(
-            ctx,
-            &mint,
-            *current_percentage,
-            target_percentage,
-            total_fund_value
-        )?;
-
-        if current_percentage < &target_percentage {
-            buy_tokens(ctx, &mint, amount_to_adjust)?;
-        } else if current_percentage > &target_percentage {
-            sell_tokens(ctx, &mint, amount_to_adjust)?;
+#[derive(Accounts)]
+pub struct PlanRebalance<'info> {
+    pub fund: Account<'info, Fund>, // Oracle program and price age limit
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(constraint = fund_account.owner == fund_authority.key())]
+    pub fund_account: Account<'info, TokenAccount>, // Base currency the holdings are traded against
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults and price oracles referenced by the allocation
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+    Hold,
+}
+
+/// A single leg of a rebalance, as `rebalance_holdings` would execute it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PlannedTrade {
+    pub mint: Pubkey,
+    pub current_weight_bps: u64,
+    pub target_weight_bps: u64,
+    pub side: TradeSide,
+    pub amount: u64,         // Token amount to buy or sell
+    pub expected_price: u64, // Oracle price used to size the trade
+}
+
+pub fn rebalance_holdings(mut ctx: Context<ManageHoldings>) -> ProgramResult {
+    let share_mint = ShareMint {
+        mint: ctx.accounts.share_mint.clone(),
+        fee_recipient: ctx.accounts.fee_recipient.clone(),
+        fund_authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.share_token_program.clone(),
+    };
+    accrue_management_fee(&mut ctx.accounts.fund, &share_mint, ctx.accounts.clock.unix_timestamp)?;
+
+    // Build the same plan that `plan_rebalance` reports, then execute it
+    let base_value = base_currency_value(&ctx.accounts.allocation_pda, &ctx.accounts.fund_account);
+    let plan = build_rebalance_plan(&ctx.accounts.fund, &ctx.accounts.allocation_pda, base_value, &ctx.accounts.token_accounts)?;
+    let now = ctx.accounts.clock.unix_timestamp;
+    let venues = parse_venues(ctx.program_id, &ctx.accounts.fund.key(), &ctx.accounts.token_accounts)?;
+
+    // Sells go first, so buys can spend the base currency they free up
+    let sells = plan.iter().filter(|trade| trade.side == TradeSide::Sell);
+    let buys = plan.iter().filter(|trade| trade.side == TradeSide::Buy);
+
+    for trade in sells.chain(buys) {
+        // Every trade counts against the per-trade and per-epoch notional caps
+        let notional = trade.amount
+            .checked_mul(trade.expected_price)
+            .ok_or(ProgramError::InvalidArgument)?;
+        record_trade_notional(&mut ctx.accounts.fund, notional, now)?;
+
+        if trade.side == TradeSide::Buy {
+            buy_tokens(&ctx, &venues, &trade.mint, trade.amount, trade.expected_price)?;
+        } else {
+            sell_tokens(&ctx, &venues, &trade.mint, trade.amount, trade.expected_price)?;
         }
     }
 
     Ok(())
 }
 
-fn calculate_amount_to_adjust(
-    ctx: &Context<ManageHoldings>,
-    mint: &Pubkey,
-    current_percentage: f64,
-    target_percentage: f64,
-    total_fund_value: u64
-) -> Result<u64, ProgramError> {
-    let market_price = get_market_price(mint)?;
-    let current_value = (current_percentage / 100.0) * total_fund_value as f64;
-    let target_value = (target_percentage / 100.0) * total_fund_value as f64;
+/// Read-only dry run of `rebalance_holdings`, meant to be called through `simulateTransaction`.
+/// The plan is logged and returned as Borsh-encoded `Vec<PlannedTrade>` return data.
+pub fn plan_rebalance(ctx: Context<PlanRebalance>) -> ProgramResult {
+    let base_value = base_currency_value(&ctx.accounts.allocation_pda, &ctx.accounts.fund_account);
+    let plan = build_rebalance_plan(&ctx.accounts.fund, &ctx.accounts.allocation_pda, base_value, &ctx.accounts.token_accounts)?;
+
+    for trade in plan.iter() {
+        msg!(
+            "Plan {}: weight {} -> {} bps, {:?} {} @ {}",
+            trade.mint,
+            trade.current_weight_bps,
+            trade.target_weight_bps,
+            trade.side,
+            trade.amount,
+            trade.expected_price
+        );
+    }
 
-    let amount_to_adjust = ((target_value - current_value) / market_price).abs();
+    let data = plan.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    set_return_data(&data);
 
-    Ok(amount_to_adjust as u64)
+    Ok(())
 }
 
-fn buy_tokens(ctx: &Context<ManageHoldings>, mint: &Pubkey, amount: u64) -> ProgramResult {
-    // Assuming you have a DEX program and accounts set up for trading
-    let dex_program_id = Pubkey::from_str("EnterYourDexProgramIdHere").unwrap();
-    let orderbook_account = find_orderbook_account(ctx, mint)?;
-    let source_token_account = &ctx.accounts.fund_account; // Your fund's SOL or stablecoin account
-    let destination_token_account = find_destination_token_account(ctx, mint)?;
-
-    // Construct the buy order instruction
-    let buy_order_ix = construct_buy_order_instruction(
-        &dex_program_id,
-        &source_token_account.key(),
-        &destination_token_account.key(),
-        &orderbook_account.key(),
-        amount,
-        serum_dex::matching::Side::Bid,
-        0, // Limit price (0 for market order)
-        amount,
-        0, // Max native PC quantity including fees (0 for unlimited)
-        serum_dex::instruction::SelfTradeBehavior::DecrementTake,
-    )?;
-
-    // Invoke the DEX program to execute the buy order
-    msg!("Executing buy order on DEX...");
-    solana_program::program::invoke(
-        &buy_order_ix,
-        &[
-            ctx.accounts.token_program.to_account_info(),
-            source_token_account.to_account_info(),
-            destination_token_account.to_account_info(),
-            orderbook_account.to_account_info(),
-            ctx.accounts.system_program.to_account_info(),
-        ],
-    )?;
-
-    msg!("Buy order executed successfully.");
-    Ok(())
+/// Base currency held in `base_vault` that isn't already counted as a holding. Oracle prices are
+/// quoted in the base currency, so its balance is its value.
+pub fn base_currency_value(allocation: &Allocation, base_vault: &Account<TokenAccount>) -> u64 {
+    let is_holding = allocation.holding_tokens.iter().any(|holding| holding.vault == base_vault.key());
+    if is_holding {
+        0
+    } else {
+        base_vault.amount
+    }
 }
 
-fn find_orderbook_account(ctx: &Context<ManageHoldings>, mint: &Pubkey) -> Result<Option<AccountInfo>, ProgramError> {
-    // map of market data that includes orderbook accounts
-    let markets = get_markets_data(); // This function should fetch or have access to market data
+/// Trades moving every holding to its target weight of the fund, where the fund is the holdings
+/// plus `base_value` of uninvested base currency
+pub fn build_rebalance_plan(fund: &Fund, allocation: &Allocation, base_value: u64, accounts: &[AccountInfo]) -> Result<Vec<PlannedTrade>, ProgramError> {
+    // Value every holding at its oracle price
+    let mut valuations = Vec::with_capacity(allocation.holding_tokens.len());
+    let mut total_value: u64 = base_value;
+
+    for holding in allocation.holding_tokens.iter() {
+        let (amount, price) = get_holding_amount_and_price(fund, accounts, holding)?;
+        let value = amount
+            .checked_mul(price)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        total_value = total_value
+            .checked_add(value)
+            .ok_or(ProgramError::InvalidArgument)?;
+        valuations.push((holding, value, price));
+    }
 
-    if let Some(market) = markets.iter().find(|market| &market.base_mint == mint || &market.quote_mint == mint) {
-        if let Some(orderbook_account_info) = ctx.accounts.token_accounts.iter().find(|account| &account.key() == &market.orderbook) {
-            return Ok(Some(orderbook_account_info.clone()));
-        }
+    let mut plan = Vec::with_capacity(valuations.len());
+
+    for (holding, current_value, price) in valuations {
+        let target_weight_bps = holding.percentage as u64 * 100;
+        let target_value = (total_value as u128 * target_weight_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        let current_weight_bps = if total_value == 0 {
+            0
+        } else {
+            (current_value as u128 * BPS_DENOMINATOR as u128 / total_value as u128) as u64
+        };
+
+        let (side, value_difference) = if current_value < target_value {
+            (TradeSide::Buy, target_value - current_value)
+        } else if current_value > target_value {
+            (TradeSide::Sell, current_value - target_value)
+        } else {
+            (TradeSide::Hold, 0)
+        };
+
+        // Convert the value difference back to a token amount at the oracle price
+        let amount = if price == 0 { 0 } else { value_difference / price };
+        let side = if amount == 0 { TradeSide::Hold } else { side };
+
+        plan.push(PlannedTrade {
+            mint: holding.token_mint,
+            current_weight_bps,
+            target_weight_bps,
+            side,
+            amount,
+            expected_price: price,
+        });
     }
-    Ok(None) // Return None if no orderbook account is found
+
+    Ok(plan)
 }
 
-struct Market {
-    base_mint: Pubkey,
-    quote_mint: Pubkey,
-    orderbook: Pubkey,
+/// Oracle value of all holdings listed in the allocation
+pub fn get_holdings_value(fund: &Fund, allocation: &Allocation, accounts: &[AccountInfo]) -> Result<u64, ProgramError> {
+    let mut total_value: u64 = 0;
+
+    for holding in allocation.holding_tokens.iter() {
+        let (amount, price) = get_holding_amount_and_price(fund, accounts, holding)?;
+        let value = amount
+            .checked_mul(price)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        total_value = total_value
+            .checked_add(value)
+            .ok_or(ProgramError::InvalidArgument)?;
+    }
+
+    Ok(total_value)
 }
 
-fn get_markets_data() -> Vec<Market> {
-    vec![
-        Market {
-            base_mint: Pubkey::from_str("BaseMintPubkeyHere").unwrap(),
-            quote_mint: Pubkey::from_str("QuoteMintPubkeyHere").unwrap(),
-            orderbook: Pubkey::from_str("OrderbookPubkeyHere").unwrap(),
-        },
-        
-    ]
+pub fn get_holding_amount_and_price(fund: &Fund, accounts: &[AccountInfo], holding: &TokenAllocation) -> Result<(u64, u64), ProgramError> {
+    // Only the vault and oracle configured for the holding are read, whatever else is passed
+    let vault = find_account_by_key(accounts, &holding.vault)?;
+    let oracle = find_account_by_key(accounts, &holding.price_oracle)?;
+    if !is_token_program(vault.owner) {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let vault_data = vault.try_borrow_data()?;
+    let vault_account = TokenAccount::try_deserialize(&mut &vault_data[..])?;
+    if vault_account.mint != holding.token_mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let price = get_current_market_price_from_raydium(fund, oracle)?;
+
+    Ok((vault_account.amount, price))
 }
 
-fn find_destination_token_account(ctx: &Context<ManageHoldings>, mint: &Pubkey) -> Result<AccountInfo, ProgramError> {
-    // Iterate over the token accounts stored in the context to find the one matching the given mint
-    ctx.accounts.token_accounts.iter()
-        .find(|account| &account.mint == mint)
-        .ok_or(ProgramError::AccountNotFound)
-        .map(|account| account.to_account_info())
+pub fn find_account_by_key<'a, 'info>(accounts: &'a [AccountInfo<'info>], key: &Pubkey) -> Result<&'a AccountInfo<'info>, ProgramError> {
+    accounts.iter()
+        .find(|account| account.key == key)
+        .ok_or(ProgramError::NotEnoughAccountKeys)
 }
 
-fn construct_buy_order_instruction(
-    dex_program_id: &Pubkey,
-    source_account_pubkey: &Pubkey,
-    destination_account_pubkey: &Pubkey,
-    orderbook_account_pubkey: &Pubkey,
+fn buy_tokens<'info>(
+    ctx: &Context<ManageHoldings<'info>>,
+    venues: &[Box<dyn SwapVenue<'info> + 'info>],
+    mint: &Pubkey,
     amount: u64,
-    side: serum_dex::matching::Side,
-    limit_price: u64,
-    max_coin_qty: u64,
-    max_native_pc_qty_including_fees: u64,
-    self_trade_behavior: serum_dex::instruction::SelfTradeBehavior
-) -> Result<Instruction, ProgramError> {
-    let data = serum_dex::instruction::NewOrderV3 {
-        side,
-        limit_price,
-        max_coin_qty,
-        max_native_pc_qty_including_fees,
-        self_trade_behavior,
-        order_type: serum_dex::matching::OrderType::Limit,
-        client_order_id: 0,
-        limit: 65535,
-    };
+    expected_price: u64,
+) -> ProgramResult {
+    // Spend the base currency worth `amount` at the oracle price, accepting slippage on the tokens received
+    let amount_in = amount
+        .checked_mul(expected_price)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let minimum_amount_out = apply_slippage(&ctx.accounts.fund, amount)?;
 
-    let accounts = vec![
-        AccountMeta::new_readonly(*dex_program_id, false),
-        AccountMeta::new(*source_account_pubkey, true), // Source account (payer
-        AccountMeta::new(*orderbook_account_pubkey, false), // Orderbook account
-        AccountMeta::new(*destination_account_pubkey, false), // Destination account (to receive tokens)
-        AccountMeta::new_readonly(spl_token::id(), false), // SPL Token program
-        AccountMeta::new_readonly(sysvar::rent::id(), false), // Rent sysvar
-        AccountMeta::new_readonly(sysvar::clock::id(), false), // Clock sysvar
-        
-    ];
-
-    let instruction = Instruction {
-        program_id: *dex_program_id,
-        accounts,
-        data: serum_dex::instruction::MarketInstruction::NewOrderV3(data).pack(),
-    };
+    msg!("Executing buy order...");
+    let leg = holding_swap_leg(ctx, mint, true)?;
+    swap_for_fund(ctx, venues, &leg, amount_in, minimum_amount_out)?;
 
-    Ok(instruction)
+    msg!("Buy order executed successfully.");
+    Ok(())
 }
 
-pub fn sell_tokens(ctx: &Context<ManageHoldings>, mint: &Pubkey, amount: u64) -> ProgramResult {
-    // Assuming you have a DEX program and accounts set up for trading
-    let dex_program_id = Pubkey::from_str("EnterYourDexProgramIdHere").unwrap();
-    let orderbook_account = find_orderbook_account(ctx, mint)?;
-    let source_token_account = find_source_token_account(ctx, mint)?; // The account holding the tokens to sell
-    let destination_token_account = find_destination_token_account(ctx, mint)?; // Typically a USD or stablecoin account
-
-    // Construct the sell order instruction
-    let sell_order_ix = construct_sell_order_instruction(
-        &dex_program_id,
-        &source_token_account.key(),
-        &destination_token_account.key(),
-        &orderbook_account.key(),
-        amount,
-        serum_dex::matching::Side::Ask, // Selling tokens
-        0, // Limit price (0 for market order)
-        amount,
-        0, // Max native PC quantity including fees (0 for unlimited)
-        serum_dex::instruction::SelfTradeBehavior::DecrementTake,
-    );
+pub fn sell_tokens<'info>(
+    ctx: &Context<ManageHoldings<'info>>,
+    venues: &[Box<dyn SwapVenue<'info> + 'info>],
+    mint: &Pubkey,
+    amount: u64,
+    expected_price: u64,
+) -> ProgramResult {
+    let expected_out = amount
+        .checked_mul(expected_price)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let minimum_amount_out = apply_slippage(&ctx.accounts.fund, expected_out)?;
 
-    // Invoke the DEX program to execute the sell order
-    msg!("Executing sell order on DEX...");
-    solana_program::program::invoke(
-        &sell_order_ix,
-        &[
-            ctx.accounts.token_program.to_account_info(),
-            source_token_account.to_account_info(),
-            destination_token_account.to_account_info(),
-            orderbook_account.to_account_info(),
-            ctx.accounts.system_program.to_account_info(),
-        ],
-    )?;
+    msg!("Executing sell order...");
+    let leg = holding_swap_leg(ctx, mint, false)?;
+    swap_for_fund(ctx, venues, &leg, amount, minimum_amount_out)?;
 
     msg!("Sell order executed successfully.");
     Ok(())
 }
 
-fn construct_sell_order_instruction(
-    dex_program_id: &Pubkey,
-    source_account_pubkey: &Pubkey,
-    destination_account_pubkey: &Pubkey,
-    orderbook_account_pubkey: &Pubkey,
-    amount: u64,
-    side: serum_dex::matching::Side,
-    limit_price: u64,
-    max_coin_qty: u64,
-    max_native_pc_qty_including_fees: u64,
-    self_trade_behavior: serum_dex::instruction::SelfTradeBehavior
-) -> Instruction {
-    // Constructing a sell order similar to the buy order but with the Side::Ask
-    let data = serum_dex::instruction::NewOrderV3 {
-        side,
-        limit_price,
-        max_coin_qty,
-        max_native_pc_qty_including_fees,
-        self_trade_behavior,
-        order_type: serum_dex::matching::OrderType::Limit,
-        client_order_id: 0,
-        limit: 65535,
-    };
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*dex_program_id, false),
-        AccountMeta::new(*source_account_pubkey, true), // Source account (payer)
-        AccountMeta::new(*orderbook_account_pubkey, false), // Orderbook account
-        AccountMeta::new(*destination_account_pubkey, false), // Destination account (to receive currency)
-        AccountMeta::new_readonly(spl_token::id(), false), // SPL Token program
-        AccountMeta::new_readonly(sysvar::rent::id(), false), // Rent sysvar
-        AccountMeta::new_readonly(sysvar::clock::id(), false), // Clock sysvar
-        // Add other necessary accounts like market, open orders, etc.
-    ];
-
-    let instruction = Instruction {
-        program_id: *dex_program_id,
-        accounts,
-        data: serum_dex::instruction::MarketInstruction::NewOrderV3(data).pack(),
+/// Swap leg between the fund's base currency account and a holding's vault
+fn holding_swap_leg<'info>(ctx: &Context<ManageHoldings<'info>>, mint: &Pubkey, is_buy: bool) -> Result<SwapLeg<'info>, ProgramError> {
+    let holding = ctx.accounts.allocation_pda.holding_tokens.iter()
+        .find(|holding| holding.token_mint == *mint)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let holding_vault = find_account_by_key(&ctx.accounts.token_accounts, &holding.vault)?.clone();
+    let base_account = ctx.accounts.fund_account.to_account_info();
+    let base_mint = ctx.accounts.fund_account.mint;
+
+    let (input_mint, output_mint, source, destination) = if is_buy {
+        (base_mint, *mint, base_account, holding_vault)
+    } else {
+        (*mint, base_mint, holding_vault, base_account)
     };
 
-    Ok(instruction)
+    Ok(SwapLeg {
+        input_mint,
+        output_mint,
+        source,
+        destination,
+        authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    })
 }
 
-fn find_source_token_account(ctx: &Context<ManageHoldings>, mint: &Pubkey) -> Result<AccountInfo, ProgramError> {
-    // This function finds the source token account for selling tokens
-    // It should match the mint of the token you want to sell
-    ctx.accounts.token_accounts.iter()
-        .find(|account| &account.mint == mint)
-        .ok_or(ProgramError::AccountNotFound)
-        .map(|account| account.to_account_info())
-}
+fn swap_for_fund<'info>(
+    ctx: &Context<ManageHoldings<'info>>,
+    venues: &[Box<dyn SwapVenue<'info> + 'info>],
+    leg: &SwapLeg<'info>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
 
-fn find_destination_token_account(ctx: &Context<ManageHoldings>, mint: &Pubkey) -> Result<AccountInfo, ProgramError> {
-    // This function finds the destination token account for buying tokens
-    // It should match the mint of the token you want to buy
-    ctx.accounts.token_accounts.iter()
-        .find(|account| &account.mint == mint)
-        .ok_or(ProgramError::AccountNotFound)
-        .map(|account| account.to_account_info())
+    route_swap(venues, leg, amount_in, minimum_amount_out, &[&seeds[..]])
 }
 
 pub fn distribute_holdings_evenly(ctx: Context<ManageHoldings>) -> ProgramResult {
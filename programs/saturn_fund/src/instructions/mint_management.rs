@@ -1,17 +1,533 @@
 // In mint_management.rs
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token};
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed},
+    program_pack::Pack,
+    system_instruction,
+};
+use anchor_spl::token::{self, Token};
+use spl_token_2022::extension::{
+    metadata_pointer::instruction as metadata_pointer_instruction,
+    transfer_fee::instruction as transfer_fee_instruction,
+    transfer_hook::instruction as transfer_hook_instruction,
+    ExtensionType,
+};
+use spl_token_2022::instruction::{self as token_2022_instruction, AuthorityType};
+use spl_token_2022::state::Mint as Mint2022;
+use crate::error::SaturnFundError;
+use crate::instructions::fee_handling::{check_fee_bounds, is_token_program, TRANSFER_FEE_BPS_BOUNDS};
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
 
 #[derive(Accounts)]
 pub struct CreateMintAccount<'info> {
-    #[account(init, payer = user, mint::decimals = 9, mint::authority = mint_authority)]
-    pub mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub mint: Signer<'info>, // Fresh keypair for the share mint
     #[account(mut)]
     pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
+    #[account(mut, constraint = fund.owner == user.key())]
+    pub fund: Account<'info, Fund>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = metadata.key() == metadata_address(&mint.key()))]
+    pub metadata: AccountInfo<'info>, // Metaplex metadata account of the new mint
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Creates the $STRN share mint together with its Metaplex metadata, so wallets show the fund's
+/// name and symbol. The fund PDA is the mint authority and holds the metadata update authority.
+/// With `freezable` it is also the freeze authority, letting a permissioned fund freeze holders.
+pub fn create_mint(ctx: Context<CreateMintAccount>, name: String, symbol: String, uri: String, freezable: bool) -> ProgramResult {
+    // Replacing the share mint would orphan every share already issued
+    if ctx.accounts.fund.share_mint != Pubkey::default() {
+        return Err(SaturnFundError::ShareMintAlreadySet.into());
+    }
+
+    let mint = &ctx.accounts.mint;
+    let fund_authority = ctx.accounts.fund_authority.key;
+    let lamports = ctx.accounts.rent.minimum_balance(Mint2022::LEN);
+
+    invoke(
+        &system_instruction::create_account(ctx.accounts.user.key, mint.key, lamports, Mint2022::LEN as u64, &token::ID),
+        &[
+            ctx.accounts.user.to_account_info(),
+            mint.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+    )?;
+    invoke(
+        &token_2022_instruction::initialize_mint2(
+            &token::ID,
+            mint.key,
+            fund_authority,
+            freeze_authority(fund_authority, freezable),
+            SHARE_DECIMALS,
+        )?,
+        &[mint.to_account_info(), ctx.accounts.token_program.to_account_info()],
+    )?;
+
+    let fund = &ctx.accounts.fund;
+    create_share_metadata(
+        fund,
+        ShareMetadataAccounts {
+            metadata: ctx.accounts.metadata.clone(),
+            mint: ctx.accounts.mint.to_account_info(),
+            fund_authority: ctx.accounts.fund_authority.clone(),
+            payer: ctx.accounts.user.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+            token_metadata_program: ctx.accounts.token_metadata_program.clone(),
+        },
+        name,
+        symbol,
+        uri,
+    )?;
+
+    ctx.accounts.fund.share_mint = ctx.accounts.mint.key();
+
+    msg!("Share mint {} created with the fund PDA as mint authority (freezable: {}).", ctx.accounts.mint.key(), freezable);
+    Ok(())
+}
+
+// Decimals of a share mint created by `create_mint`
+const SHARE_DECIMALS: u8 = 9;
+
+/// The fund PDA as freeze authority, only when the mint should be freezable
+fn freeze_authority(fund_authority: &Pubkey, freezable: bool) -> Option<&Pubkey> {
+    if freezable {
+        Some(fund_authority)
+    } else {
+        None
+    }
+}
+
+#[derive(Accounts)]
+pub struct RenounceFreezeAuthority<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(mut, constraint = mint.key() == fund.share_mint && *mint.owner == share_token_program.key())]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(constraint = is_token_program(&share_token_program.key()))]
+    pub share_token_program: AccountInfo<'info>, // SPL Token or Token-2022, whichever owns the share mint
+}
+
+/// Permanently removes the freeze authority from the share mint. There is no way to set it
+/// again, so holders can rely on their shares never being frozen afterwards.
+pub fn renounce_freeze_authority(ctx: Context<RenounceFreezeAuthority>) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &token_2022_instruction::set_authority(
+            ctx.accounts.share_token_program.key,
+            ctx.accounts.mint.key,
+            None,
+            AuthorityType::FreezeAccount,
+            ctx.accounts.fund_authority.key,
+            &[],
+        )?,
+        &[
+            ctx.accounts.mint.clone(),
+            ctx.accounts.fund_authority.clone(),
+            ctx.accounts.share_token_program.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    msg!("Freeze authority renounced on share mint {}", ctx.accounts.mint.key());
+    Ok(())
+}
+
+/// Metaplex token metadata program
+pub mod mpl_token_metadata {
+    anchor_lang::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+/// Metaplex metadata account of a mint
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", mpl_token_metadata::ID.as_ref(), mint.as_ref()],
+        &mpl_token_metadata::ID,
+    ).0
+}
+
+// Metaplex token metadata instruction tags and field limits
+const CREATE_METADATA_ACCOUNT_V3_TAG: u8 = 33;
+const UPDATE_METADATA_ACCOUNT_V2_TAG: u8 = 15;
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_SYMBOL_LENGTH: usize = 10;
+const MAX_URI_LENGTH: usize = 200;
+// Offset of the name in a metadata account: key, update authority, mint
+const METADATA_NAME_OFFSET: usize = 1 + 32 + 32;
+
+/// Accounts the Metaplex program needs to create a mint's metadata
+pub struct ShareMetadataAccounts<'info> {
+    pub metadata: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub fund_authority: AccountInfo<'info>, // Mint authority and update authority
+    pub payer: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    pub token_metadata_program: AccountInfo<'info>,
+}
+
+/// Borsh encoding of Metaplex `DataV2` without creators, collection or uses
+fn metadata_data_v2(name: &str, symbol: &str, uri: &str) -> Result<Vec<u8>, ProgramError> {
+    if name.len() > MAX_NAME_LENGTH || symbol.len() > MAX_SYMBOL_LENGTH || uri.len() > MAX_URI_LENGTH {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut data = (name, symbol, uri, 0u16).try_to_vec()?; // No seller fee on fund shares
+    data.extend_from_slice(&[0, 0, 0]);                       // No creators, collection or uses
+    Ok(data)
+}
+
+pub fn create_share_metadata(
+    fund: &Account<Fund>,
+    accounts: ShareMetadataAccounts,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> ProgramResult {
+    let mut data = vec![CREATE_METADATA_ACCOUNT_V3_TAG];
+    data.extend(metadata_data_v2(&name, &symbol, &uri)?);
+    data.push(1); // Mutable, so the URI can be updated later
+    data.push(0); // No collection details
+
+    let ix = Instruction {
+        program_id: mpl_token_metadata::ID,
+        accounts: vec![
+            AccountMeta::new(*accounts.metadata.key, false),
+            AccountMeta::new_readonly(*accounts.mint.key, false),
+            AccountMeta::new_readonly(*accounts.fund_authority.key, true),
+            AccountMeta::new(*accounts.payer.key, true),
+            AccountMeta::new_readonly(*accounts.fund_authority.key, true),
+            AccountMeta::new_readonly(*accounts.system_program.key, false),
+            AccountMeta::new_readonly(*accounts.rent.key, false),
+        ],
+        data,
+    };
+
+    let fund_key = fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[fund.authority_bump]];
+    invoke_signed(
+        &ix,
+        &[
+            accounts.metadata,
+            accounts.mint,
+            accounts.fund_authority,
+            accounts.payer,
+            accounts.system_program,
+            accounts.rent,
+            accounts.token_metadata_program,
+        ],
+        &[&seeds[..]],
+    )?;
+
+    msg!("Share metadata created: {} ({})", name, symbol);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateShareMetadata<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = metadata.key() == metadata_address(&fund.share_mint))]
+    pub metadata: AccountInfo<'info>,
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
+}
+
+/// Points the share metadata at a new URI, keeping its name and symbol
+pub fn update_share_metadata_uri(ctx: Context<UpdateShareMetadata>, uri: String) -> ProgramResult {
+    let (name, symbol) = {
+        let data = ctx.accounts.metadata.try_borrow_data()?;
+        read_metadata_name_and_symbol(&data)?
+    };
+
+    let mut data = vec![UPDATE_METADATA_ACCOUNT_V2_TAG, 1];
+    data.extend(metadata_data_v2(&name, &symbol, &uri)?);
+    data.extend_from_slice(&[0, 0, 0]); // Keep the update authority, primary sale flag and mutability
+
+    let ix = Instruction {
+        program_id: mpl_token_metadata::ID,
+        accounts: vec![
+            AccountMeta::new(ctx.accounts.metadata.key(), false),
+            AccountMeta::new_readonly(ctx.accounts.fund_authority.key(), true),
+        ],
+        data,
+    };
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.metadata.clone(),
+            ctx.accounts.fund_authority.clone(),
+            ctx.accounts.token_metadata_program.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    msg!("Share metadata URI set to {}", uri);
+    Ok(())
+}
+
+/// Name and symbol of a metadata account, without the padding Metaplex stores them with
+fn read_metadata_name_and_symbol(data: &[u8]) -> Result<(String, String), ProgramError> {
+    let mut cursor = data.get(METADATA_NAME_OFFSET..).ok_or(ProgramError::InvalidAccountData)?;
+    let name = String::deserialize(&mut cursor).map_err(|_| ProgramError::InvalidAccountData)?;
+    let symbol = String::deserialize(&mut cursor).map_err(|_| ProgramError::InvalidAccountData)?;
+
+    Ok((
+        name.trim_end_matches(char::from(0)).to_string(),
+        symbol.trim_end_matches(char::from(0)).to_string(),
+    ))
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ShareMintConfig {
+    pub decimals: u8,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,                       // Off-chain JSON describing the fund's shares
+    pub transfer_fee_bps: u16,             // Fee withheld on every secondary $STRN transfer
+    pub maximum_transfer_fee: u64,         // Cap on the fee withheld from a single transfer
+    pub transfer_hook_program: Option<Pubkey>, // Program checking the allowlist on transfers, if any
+    pub freezable: bool,                   // Fund PDA as freeze authority, for the permissioned mode
+}
+
+#[derive(Accounts)]
+pub struct CreateShareMint2022<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub mint: Signer<'info>, // Fresh keypair for the share mint
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(address = spl_token_2022::ID)]
+    pub token_2022_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(mut, constraint = metadata.key() == metadata_address(&mint.key()))]
+    pub metadata: AccountInfo<'info>, // Metaplex metadata the metadata pointer refers to
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
 }
 
-pub fn create_mint(ctx: Context<CreateMintAccount>) -> ProgramResult {
-    msg!("Mint account created with mint authority set to program ID.");
+/// Creates the $STRN share mint under Token-2022 with the transfer-fee and metadata-pointer
+/// extensions, and a transfer hook when a hook program is given. The fund PDA is the mint
+/// authority and the authority over every extension.
+pub fn create_share_mint_2022(ctx: Context<CreateShareMint2022>, config: ShareMintConfig) -> ProgramResult {
+    if ctx.accounts.fund.share_mint != Pubkey::default() {
+        return Err(SaturnFundError::ShareMintAlreadySet.into());
+    }
+    check_fee_bounds(config.transfer_fee_bps as u64, TRANSFER_FEE_BPS_BOUNDS)?;
+
+    let mint = &ctx.accounts.mint;
+    let fund_authority = ctx.accounts.fund_authority.key;
+    let token_2022 = &ctx.accounts.token_2022_program;
+
+    let mut extensions = vec![ExtensionType::TransferFeeConfig, ExtensionType::MetadataPointer];
+    if config.transfer_hook_program.is_some() {
+        extensions.push(ExtensionType::TransferHook);
+    }
+    let space = ExtensionType::try_calculate_account_len::<Mint2022>(&extensions)?;
+    let lamports = ctx.accounts.rent.minimum_balance(space);
+
+    invoke(
+        &system_instruction::create_account(ctx.accounts.owner.key, mint.key, lamports, space as u64, &spl_token_2022::ID),
+        &[
+            ctx.accounts.owner.to_account_info(),
+            mint.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+    )?;
+
+    // Extensions must be initialized before the mint itself
+    invoke(
+        &transfer_fee_instruction::initialize_transfer_fee_config(
+            &spl_token_2022::ID,
+            mint.key,
+            Some(fund_authority),
+            Some(fund_authority),
+            config.transfer_fee_bps,
+            config.maximum_transfer_fee,
+        )?,
+        &[mint.to_account_info(), token_2022.clone()],
+    )?;
+    invoke(
+        &metadata_pointer_instruction::initialize(
+            &spl_token_2022::ID,
+            mint.key,
+            Some(*fund_authority),
+            Some(metadata_address(mint.key)),
+        )?,
+        &[mint.to_account_info(), token_2022.clone()],
+    )?;
+    if let Some(hook_program) = config.transfer_hook_program {
+        invoke(
+            &transfer_hook_instruction::initialize(&spl_token_2022::ID, mint.key, Some(*fund_authority), Some(hook_program))?,
+            &[mint.to_account_info(), token_2022.clone()],
+        )?;
+    }
+    invoke(
+        &token_2022_instruction::initialize_mint2(
+            &spl_token_2022::ID,
+            mint.key,
+            fund_authority,
+            freeze_authority(fund_authority, config.freezable),
+            config.decimals,
+        )?,
+        &[mint.to_account_info(), token_2022.clone()],
+    )?;
+
+    create_share_metadata(
+        &ctx.accounts.fund,
+        ShareMetadataAccounts {
+            metadata: ctx.accounts.metadata.clone(),
+            mint: mint.to_account_info(),
+            fund_authority: ctx.accounts.fund_authority.clone(),
+            payer: ctx.accounts.owner.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+            token_metadata_program: ctx.accounts.token_metadata_program.clone(),
+        },
+        config.name,
+        config.symbol,
+        config.uri,
+    )?;
+
+    let fund = &mut ctx.accounts.fund;
+    fund.share_mint = mint.key();
+    fund.transfer_fee_bps = config.transfer_fee_bps as u64;
+
+    msg!("Token-2022 share mint {} created with a {} bps transfer fee", mint.key(), config.transfer_fee_bps);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ManageShareMint2022<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(mut, constraint = mint.key() == fund.share_mint)]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(address = spl_token_2022::ID)]
+    pub token_2022_program: AccountInfo<'info>,
+}
+
+/// Changes the transfer fee on the share mint. Token-2022 applies it two epochs later.
+pub fn set_share_transfer_fee(ctx: Context<ManageShareMint2022>, transfer_fee_bps: u16, maximum_transfer_fee: u64) -> ProgramResult {
+    check_fee_bounds(transfer_fee_bps as u64, TRANSFER_FEE_BPS_BOUNDS)?;
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &transfer_fee_instruction::set_transfer_fee(
+            &spl_token_2022::ID,
+            ctx.accounts.mint.key,
+            ctx.accounts.fund_authority.key,
+            &[],
+            transfer_fee_bps,
+            maximum_transfer_fee,
+        )?,
+        &[
+            ctx.accounts.mint.clone(),
+            ctx.accounts.fund_authority.clone(),
+            ctx.accounts.token_2022_program.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    ctx.accounts.fund.transfer_fee_bps = transfer_fee_bps as u64;
+
+    msg!("Share transfer fee set to {} bps", transfer_fee_bps);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CollectShareTransferFees<'info> {
+    pub fund: Account<'info, Fund>,
+    #[account(mut, constraint = mint.key() == fund.share_mint)]
+    pub mint: AccountInfo<'info>,
+    #[account(mut, constraint = fee_recipient.key() == fund.fee_recipient)]
+    pub fee_recipient: AccountInfo<'info>, // $STRN account receiving the withheld transfer fees
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(address = spl_token_2022::ID)]
+    pub token_2022_program: AccountInfo<'info>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // $STRN accounts holding withheld transfer fees
+}
+
+/// Permissionless crank sweeping withheld transfer fees into the mint and paying them to the fee recipient
+pub fn collect_share_transfer_fees(ctx: Context<CollectShareTransferFees>) -> ProgramResult {
+    let token_2022 = &ctx.accounts.token_2022_program;
+    let mint = &ctx.accounts.mint;
+
+    if !ctx.accounts.token_accounts.is_empty() {
+        let sources: Vec<&Pubkey> = ctx.accounts.token_accounts.iter().map(|account| account.key).collect();
+        let mut infos = vec![mint.clone()];
+        infos.extend(ctx.accounts.token_accounts.iter().cloned());
+        infos.push(token_2022.clone());
+
+        invoke(
+            &transfer_fee_instruction::harvest_withheld_tokens_to_mint(&spl_token_2022::ID, mint.key, &sources)?,
+            &infos,
+        )?;
+    }
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &transfer_fee_instruction::withdraw_withheld_tokens_from_mint(
+            &spl_token_2022::ID,
+            mint.key,
+            ctx.accounts.fee_recipient.key,
+            ctx.accounts.fund_authority.key,
+            &[],
+        )?,
+        &[
+            mint.clone(),
+            ctx.accounts.fee_recipient.clone(),
+            ctx.accounts.fund_authority.clone(),
+            token_2022.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    msg!("Withheld share transfer fees collected");
     Ok(())
 }
@@ -1,95 +1,166 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, MintTo, TokenAccount, Transfer};
-use solana_program::program::invoke;
+use anchor_spl::token::{self, spl_token::native_mint, TokenAccount};
+use solana_program::program::invoke_signed;
 use solana_program::program_pack::Pack;
 use solana_program::sysvar::rent::Rent;
 use solana_program::sysvar::Sysvar;
+use crate::instructions::calculate_price_of_fund::{get_fund_value, shares_for_deposit};
+use crate::instructions::fee_handling::{accrue_management_fee, apply_fee, check_share_account, is_token_program, mint_fee_bps_for, share_supply, ShareMint};
+use crate::instructions::fund_operation::enforce_transfer_restrictions;
+use crate::instructions::raydium_integration::get_current_market_price_from_raydium;
+use crate::instructions::native_sol::wrap_sol;
+use crate::instructions::referral::credit_referrer;
+use crate::instructions::swap_venue::{parse_venues, route_swap, SwapLeg};
+use crate::instructions::trade_limits::min_amount_out;
+use crate::state::allocation::Allocation;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+use crate::state::position::{UserPosition, USER_POSITION_SEED};
 
 #[derive(Accounts)]
 pub struct MintToken<'info> {
     #[account(mut)]
     pub user: Signer<'info>, // User is still the signer to pay for transaction fees
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub holding_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub fund_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    #[account(mut, constraint = *user_token_account.owner == share_token_program.key())]
+    pub user_token_account: AccountInfo<'info>, // Receives the $STRN, under whichever program owns the share mint
+    #[account(mut, constraint = holding_account.mint == native_mint::ID && holding_account.owner == fund_authority.key())]
+    pub holding_account: Account<'info, TokenAccount>, // Fund wSOL vault kept for redemptions
+    #[account(mut, constraint = fund_account.mint == native_mint::ID && fund_account.owner == fund_authority.key())]
+    pub fund_account: Account<'info, TokenAccount>, // Fund wSOL vault invested into holdings
+    #[account(mut, constraint = mint.key() == fund.share_mint && *mint.owner == share_token_program.key())]
+    pub mint: AccountInfo<'info>,
+    #[account(constraint = is_token_program(&share_token_program.key()))]
+    pub share_token_program: AccountInfo<'info>, // SPL Token or Token-2022, whichever owns the share mint
     pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
     pub config_account: Account<'info, TokenAccount>, // Configuration account
-    /// ADD: Program's authority account, possibly a PDA
     #[account(mut)]
-    pub program_authority: AccountInfo<'info>,
+    pub fund: Account<'info, Fund>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>, // Oracles configured for each holding
+    #[account(constraint = allocation_pda.price_oracle(&fund_account.mint) == Some(source_price_oracle.key()))]
+    pub source_price_oracle: AccountInfo<'info>, // Price of the token being swapped out of
+    #[account(constraint = allocation_pda.price_oracle(&swap_destination.mint) == Some(destination_price_oracle.key()))]
+    pub destination_price_oracle: AccountInfo<'info>, // Price of the token being acquired
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(mut, constraint = swap_destination.owner == fund_authority.key())]
+    pub swap_destination: Account<'info, TokenAccount>, // Holding vault receiving the swapped half
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>, // Receives accrued management fees
+    pub clock: Sysvar<'info, Clock>,
+    #[account(mut, constraint = fee_account.key() == fund.fee_vault && fee_account.mint == native_mint::ID)]
+    pub fee_account: Account<'info, TokenAccount>, // Fee vault, receives the mint fee
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, fund.key().as_ref(), user.key().as_ref()],
+        bump = user_position.bump,
+    )]
+    pub user_position: Account<'info, UserPosition>, // Tracks the user's deposit time for redeem fees
+    // The vaults and oracles valuing the fund, swap venue account groups, the referrer's account when given,
+    // and the user's allowlist entry in restricted mode are passed as remaining accounts
 }
 
-pub fn handler(ctx: Context<MintToken>, amount: u64) -> ProgramResult {
+/// Mints $STRN for `amount` lamports of native SOL, which are wrapped into the fund's wSOL vaults
+pub fn handler(ctx: Context<MintToken>, amount: u64, referrer: Option<Pubkey>) -> ProgramResult {
+    enforce_transfer_restrictions(ctx.program_id, &ctx.accounts.fund, ctx.remaining_accounts, &ctx.accounts.user.key())?;
+    // Shares go to the depositor's own account, so the allowlist check above covers the holder
+    let share_owner = check_share_account(&ctx.accounts.fund, ctx.accounts.share_token_program.key, &ctx.accounts.user_token_account)?;
+    if share_owner != ctx.accounts.user.key() {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    // Bring fees up to date before new shares are issued
+    let share_mint = ShareMint {
+        mint: ctx.accounts.mint.clone(),
+        fee_recipient: ctx.accounts.fee_recipient.clone(),
+        fund_authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.share_token_program.clone(),
+    };
+    accrue_management_fee(&mut ctx.accounts.fund, &share_mint, ctx.accounts.clock.unix_timestamp)?;
+
+    // Shares are issued for the deposit net of the mint fee, which is tiered by deposit size
+    let deposit = amount;
+    let (amount, fee) = apply_fee(amount, mint_fee_bps_for(&ctx.accounts.fund, amount))?;
+    if fee > 0 {
+        deposit_sol(&ctx, &ctx.accounts.fee_account.to_account_info(), fee)?;
+    }
+    if let Some(referrer) = referrer {
+        let user = ctx.accounts.user.key();
+        credit_referrer(ctx.program_id, &mut ctx.accounts.fund, ctx.remaining_accounts, &user, &referrer, fee, deposit)?;
+    }
+
     let user_token_account = &ctx.accounts.user_token_account;
     let fund_account = &ctx.accounts.fund_account;
     let holding_account = &ctx.accounts.holding_account;
     let mint = &ctx.accounts.mint;
-    let token_program = &ctx.accounts.token_program;
-    let program_authority = &ctx.accounts.program_authority;
 
     // Step 1: Calculate the amount of $STRN to mint
-    let strn_amount = calculate_strn_amount(amount)?;
-
-    // Step 2: Mint $STRN to the user
-    token::mint_to(
-        CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            MintTo {
-                mint: mint.to_account_info(),
-                to: user_token_account.to_account_info(),
-                authority: program_authority.to_account_info(),
-            },
-            &[&program_authority.key], // Signer seeds if program_authority is a PDA
-        ),
-        strn_amount,
+    let strn_amount = calculate_strn_amount(&ctx, amount)?;
+
+    // Step 2: Mint $STRN to the user, signed by the fund PDA holding the mint authority
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &spl_token_2022::instruction::mint_to(
+            ctx.accounts.share_token_program.key,
+            mint.key,
+            user_token_account.key,
+            ctx.accounts.fund_authority.key,
+            &[],
+            strn_amount,
+        )?,
+        &[
+            mint.clone(),
+            user_token_account.clone(),
+            ctx.accounts.fund_authority.clone(),
+            ctx.accounts.share_token_program.clone(),
+        ],
+        &[&seeds[..]],
     )?;
+    ctx.accounts.user_position.record_deposit(strn_amount, ctx.accounts.clock.unix_timestamp)?;
 
     // Step 3: Determine fund allocation
     let target_holding_amount = get_target_holding_amount(&ctx)?;
-    let holding_balance = get_balance(holding_account)?;
-    let fund_balance = get_balance(fund_account)?;
+    let holding_balance = holding_account.amount;
 
     if holding_balance < target_holding_amount {
         let to_holding = std::cmp::min(amount, target_holding_amount - holding_balance);
         let to_fund = amount - to_holding;
 
-        // Step 4a: Transfer to holding account
-        transfer_to_account(program_authority, holding_account, to_holding)?;
+        // Step 4a: Wrap into the holding account
+        deposit_sol(&ctx, &holding_account.to_account_info(), to_holding)?;
 
-        // Step 4b: Transfer remaining to fund account
+        // Step 4b: Wrap the remainder into the fund account
         if to_fund > 0 {
-            transfer_to_account(program_authority, fund_account, to_fund)?;
-            allocate_into_holdings(fund_account, to_fund)?;
+            deposit_sol(&ctx, &fund_account.to_account_info(), to_fund)?;
+            allocate_into_holdings(&ctx, &fund_account.to_account_info(), to_fund)?;
         }
     } else {
-        // Step 4c: Transfer directly to fund account
-        transfer_to_account(program_authority, fund_account, amount)?;
-        allocate_into_holdings(fund_account, amount)?;
+        // Step 4c: Wrap directly into the fund account
+        deposit_sol(&ctx, &fund_account.to_account_info(), amount)?;
+        allocate_into_holdings(&ctx, &fund_account.to_account_info(), amount)?;
     }
 
     Ok(())
 }
 
-fn calculate_strn_amount(amount: u64) -> Result<u64, ProgramError> {
-    // Assume 1 SOL = 100 $STRN for simplicity
-    let base_rate: u64 = 100;
-
-    // Calculate the base amount of $STRN
-    let base_amount = amount * base_rate;
+// $STRN for `amount` lamports at the fund's current value per share, net of vesting profit
+fn calculate_strn_amount(ctx: &Context<MintToken>, amount: u64) -> Result<u64, ProgramError> {
+    let now = ctx.accounts.clock.unix_timestamp;
+    let fund_value = get_fund_value(&ctx.accounts.fund, &ctx.accounts.allocation_pda, ctx.remaining_accounts, now)?;
+    let supply = share_supply(&ctx.accounts.mint)?;
 
-    // Apply ±1% slippage
-    let min_amount = base_amount * 99 / 100;
-    let max_amount = base_amount * 101 / 100;
+    // The deposit is wSOL, valued at the same oracle price as the fund's SOL holdings
+    let sol_price = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.source_price_oracle)?;
+    let deposit_value = amount
+        .checked_mul(sol_price)
+        .ok_or(ProgramError::InvalidArgument)?;
 
-    // For simplicity, we return the base amount here
-    // In a real scenario, you might want to return a value within the slippage range
-    Ok(base_amount)
+    shares_for_deposit(fund_value, supply, deposit_value)
 }
 
 fn get_target_holding_amount(ctx: &Context<MintToken>) -> Result<u64, ProgramError> {
@@ -103,169 +174,47 @@ fn get_target_holding_amount(ctx: &Context<MintToken>) -> Result<u64, ProgramErr
     Ok(target_holding_amount)
 }
 
-fn get_balance(account: &AccountInfo) -> Result<u64, ProgramError> {
-    // Assuming the account data is packed in a way that we can directly read the balance
-    let account_data = account.try_borrow_data()?;
-    let balance = u64::from_le_bytes(account_data[..8].try_into().map_err(|_| ProgramError::InvalidAccountData)?);
-
-    Ok(balance)
-}
-
-fn transfer_to_account(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
-    let ix = solana_program::system_instruction::transfer(
-        from.key,
-        to.key,
+// Wraps `amount` lamports of the user's native SOL into one of the fund's wSOL vaults
+fn deposit_sol(ctx: &Context<MintToken>, vault: &AccountInfo, amount: u64) -> ProgramResult {
+    wrap_sol(
+        &ctx.accounts.user.to_account_info(),
+        vault,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.token_program.to_account_info(),
         amount,
-    );
-
-    invoke(
-        &ix,
-        &[
-            from.clone(),
-            to.clone(),
-        ],
     )
 }
 
-fn allocate_into_holdings(fund_account: &AccountInfo, amount: u64) -> ProgramResult {
+fn allocate_into_holdings(ctx: &Context<MintToken>, fund_account: &AccountInfo, amount: u64) -> ProgramResult {
     // Split the amount into 50% SOL and 50% for swapping
     let sol_amount = amount / 2;
     let swap_amount = amount - sol_amount; // To handle odd amounts
 
     // Keep 50% of the SOL in the fund account (already done by transferring to fund_account)
-    // Swap the remaining 50% via Raydium
-    swap_via_raydium(fund_account, swap_amount)?;
+    // Swap the remaining 50% on the best venue, bounded by the oracle price and slippage tolerance
+    let price_in = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.source_price_oracle)?;
+    let price_out = get_current_market_price_from_raydium(&ctx.accounts.fund, &ctx.accounts.destination_price_oracle)?;
+    let minimum_amount_out = min_amount_out(&ctx.accounts.fund, swap_amount, price_in, price_out)?;
+    swap_into_holding(ctx, fund_account, swap_amount, minimum_amount_out)?;
 
-    // Increase liquidity on Raydium with the swapped tokens
-    increase_liquidity_on_raydium(fund_account, swap_amount)?;
+    // Liquidity is added separately through `deposit_liquidity`, which sizes both sides from pool reserves
 
     Ok(())
 }
 
-fn swap_via_raydium(ctx: &Context<MintToken>, fund_account: &AccountInfo, swap_amount: u64) -> ProgramResult {
-    // Define Raydium program ID and swap pool accounts
-    let raydium_program_id = Pubkey::from_str("4uQ...RaydiumProgramId...").unwrap();
-    let swap_pool_info = AccountInfo::new(
-        // Parameters for the swap pool account
-        &Pubkey::from_str("Swap...Pool...Pubkey...").unwrap(),
-        false,
-        true,
-        &mut [],
-        &mut [],
-        &Account::default(),
-        false,
-        Epoch::default(),
-    );
-    let swap_authority = Pubkey::from_str("Authority...Pubkey...").unwrap();
-    let source_token_account = fund_account.clone(); // Your source token account
-    let destination_token_account = fund_account.clone(); // Your destination token account, adjust as necessary
-
-    // Construct the swap instruction
-    let swap_instruction = raydium_swap_instruction(
-        raydium_program_id,
-        swap_pool_info.key,
-        swap_authority,
-        source_token_account.key,
-        destination_token_account.key,
-        swap_amount,
-    );
-
-    // Invoke the swap instruction
-    invoke(
-        &swap_instruction,
-        &[
-            ctx.accounts.token_program.to_account_info(),
-            swap_pool_info,
-            source_token_account,
-            destination_token_account,
-        ],
-    )
-}
-
-fn raydium_swap_instruction(
-    program_id: Pubkey,
-    swap_pool_pubkey: &Pubkey,
-    swap_authority: Pubkey,
-    source_token_pubkey: &Pubkey,
-    destination_token_pubkey: &Pubkey,
-    amount: u64,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*swap_pool_pubkey, false),
-        AccountMeta::new_readonly(swap_authority, false),
-        AccountMeta::new(*source_token_pubkey, false),
-        AccountMeta::new(*destination_token_pubkey, false),
-    ];
-
-    let data = vec![]; // Populate with actual data required for the swap
-
-    Instruction {
-        program_id,
-        accounts,
-        data,
-    }
+fn swap_into_holding(ctx: &Context<MintToken>, fund_account: &AccountInfo, swap_amount: u64, minimum_amount_out: u64) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    let venues = parse_venues(ctx.program_id, &fund_key, ctx.remaining_accounts)?;
+    let leg = SwapLeg {
+        input_mint: ctx.accounts.fund_account.mint,
+        output_mint: ctx.accounts.swap_destination.mint,
+        source: fund_account.clone(),
+        destination: ctx.accounts.swap_destination.to_account_info(),
+        authority: ctx.accounts.fund_authority.clone(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    };
+
+    route_swap(&venues, &leg, swap_amount, minimum_amount_out, &[&seeds[..]])
 }
-
-fn increase_liquidity_on_raydium(ctx: &Context<MintToken>, fund_account: &AccountInfo, amount: u64) -> ProgramResult {
-    // Define Raydium program ID and liquidity pool accounts
-    let raydium_program_id = Pubkey::from_str("RaydiumLiquidityProgramId").unwrap();
-    let liquidity_pool_info = AccountInfo::new(
-        // Parameters for the liquidity pool account
-        &Pubkey::from_str("LiquidityPoolPubkey").unwrap(),
-        false,
-        true,
-        &mut [],
-        &mut [],
-        &Account::default(),
-        false,
-        Epoch::default(),
-    );
-    let liquidity_authority = Pubkey::from_str("LiquidityAuthorityPubkey").unwrap();
-    let source_token_account = fund_account.clone(); // Your source token account
-    let destination_token_account = fund_account.clone(); // Your destination token account, adjust as necessary
-
-    // Construct the add liquidity instruction
-    let add_liquidity_instruction = raydium_add_liquidity_instruction(
-        raydium_program_id,
-        liquidity_pool_info.key,
-        liquidity_authority,
-        source_token_account.key,
-        destination_token_account.key,
-        amount,
-    );
-
-    // Invoke the add liquidity instruction
-    invoke(
-        &add_liquidity_instruction,
-        &[
-            ctx.accounts.token_program.to_account_info(),
-            liquidity_pool_info,
-            source_token_account,
-            destination_token_account,
-        ],
-    )
-}
-
-fn raydium_add_liquidity_instruction(
-    program_id: Pubkey,
-    liquidity_pool_pubkey: &Pubkey,
-    liquidity_authority: Pubkey,
-    source_token_pubkey: &Pubkey,
-    destination_token_pubkey: &Pubkey,
-    amount: u64,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*liquidity_pool_pubkey, false),
-        AccountMeta::new_readonly(liquidity_authority, false),
-        AccountMeta::new(*source_token_pubkey, false),
-        AccountMeta::new(*destination_token_pubkey, false),
-    ];
-
-    let data = vec![]; // Populate with actual data required for adding liquidity
-
-    Instruction {
-        program_id,
-        accounts,
-        data,
-    }
-}
\ No newline at end of file
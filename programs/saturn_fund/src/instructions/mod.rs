@@ -7,5 +7,15 @@ pub mod manage_transaction_account;
 pub mod collect_liquidity_pool_rewards;
 pub mod raydium_integration;
 pub mod burn_token;
-pub mod transfer_with_fee;
 pub mod calculate_price_of_fund;
+pub mod state;
+pub mod trade_limits;
+pub mod openbook;
+pub mod swap_venue;
+pub mod aggregator;
+pub mod raydium_farm;
+pub mod fee_distribution;
+pub mod referral;
+pub mod allowlist;
+pub mod fund_operation;
+pub mod native_sol;
@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke, program_pack::Pack, system_instruction};
+use anchor_spl::token::{self, spl_token, CloseAccount, Transfer};
+
+/// Moves `amount` lamports from `payer` into the wSOL token account `vault` and syncs its token balance
+pub fn wrap_sol<'info>(
+    payer: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    invoke(
+        &system_instruction::transfer(payer.key, vault.key, amount),
+        &[payer.clone(), vault.clone(), system_program.clone()],
+    )?;
+    invoke(
+        &spl_token::instruction::sync_native(&spl_token::ID, vault.key)?,
+        &[vault.clone(), token_program.clone()],
+    )
+}
+
+/// Accounts needed to pay native SOL out of a fund wSOL vault
+pub struct UnwrapSol<'info> {
+    pub vault: AccountInfo<'info>,          // Fund wSOL vault paying out
+    pub unwrap_account: AccountInfo<'info>, // Fresh keypair, only alive during the instruction
+    pub recipient: AccountInfo<'info>,      // Receives the SOL and funds the unwrap account's rent
+    pub native_mint: AccountInfo<'info>,
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Pays `amount` from the fund's wSOL vault to the recipient as native SOL. The wSOL passes
+/// through a temporary token account that is closed to the recipient, returning its rent too.
+pub fn unwrap_sol(accounts: &UnwrapSol, amount: u64, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let space = spl_token::state::Account::LEN;
+    invoke(
+        &system_instruction::create_account(
+            accounts.recipient.key,
+            accounts.unwrap_account.key,
+            accounts.rent.minimum_balance(space),
+            space as u64,
+            &spl_token::ID,
+        ),
+        &[
+            accounts.recipient.clone(),
+            accounts.unwrap_account.clone(),
+            accounts.system_program.clone(),
+        ],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_account3(
+            &spl_token::ID,
+            accounts.unwrap_account.key,
+            accounts.native_mint.key,
+            accounts.fund_authority.key,
+        )?,
+        &[
+            accounts.unwrap_account.clone(),
+            accounts.native_mint.clone(),
+            accounts.token_program.clone(),
+        ],
+    )?;
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            accounts.token_program.clone(),
+            Transfer {
+                from: accounts.vault.clone(),
+                to: accounts.unwrap_account.clone(),
+                authority: accounts.fund_authority.clone(),
+            },
+            signer_seeds,
+        ),
+        amount,
+    )?;
+    token::close_account(CpiContext::new_with_signer(
+        accounts.token_program.clone(),
+        CloseAccount {
+            account: accounts.unwrap_account.clone(),
+            destination: accounts.recipient.clone(),
+            authority: accounts.fund_authority.clone(),
+        },
+        signer_seeds,
+    ))
+}
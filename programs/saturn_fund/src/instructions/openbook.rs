@@ -0,0 +1,531 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, system_instruction, sysvar};
+use anchor_spl::token::{self, Mint, TokenAccount};
+use serum_dex::instruction::{self as dex_instruction, SelfTradeBehavior};
+use serum_dex::matching::{OrderType, Side};
+use serum_dex::state::{gen_vault_signer_key, Market as DexMarket};
+use std::num::NonZeroU64;
+use crate::error::SaturnFundError;
+use crate::instructions::manage_holdings::find_account_by_key;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+use crate::state::market::{HoldingMarket, HOLDING_MARKET_SEED, OPEN_ORDERS_SEED};
+use crate::BPS_DENOMINATOR;
+
+/// OpenBook v1 DEX program
+pub mod openbook_dex {
+    anchor_lang::declare_id!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
+}
+
+/// Serum v3 DEX program, which OpenBook v1 forked
+pub mod serum_dex_v3 {
+    anchor_lang::declare_id!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin");
+}
+
+/// Size of an OpenBook (Serum v3) open orders account
+const OPEN_ORDERS_SPACE: u64 = 3228;
+/// OpenBook base taker fee
+pub const OPENBOOK_TAKER_FEE_BPS: u64 = 4;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RegisterMarketArgs {
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub request_queue: Pubkey,
+    pub event_queue: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(args: RegisterMarketArgs, bump: u8, open_orders_bump: u8)]
+pub struct RegisterMarket<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init,
+        seeds = [HOLDING_MARKET_SEED, fund.key().as_ref(), base_mint.key().as_ref()],
+        bump = bump,
+        payer = owner,
+        space = 8 + HoldingMarket::LEN,
+    )]
+    pub holding_market: Account<'info, HoldingMarket>,
+    pub base_mint: Account<'info, Mint>,
+    pub quote_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [OPEN_ORDERS_SEED, fund.key().as_ref(), market.key().as_ref()],
+        bump = open_orders_bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+    #[account(constraint = *market.owner == dex_program.key())]
+    pub market: AccountInfo<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    #[account(constraint = fund_base_vault.mint == base_mint.key() && fund_base_vault.owner == fund_authority.key())]
+    pub fund_base_vault: Account<'info, TokenAccount>,
+    #[account(constraint = fund_quote_vault.mint == quote_mint.key() && fund_quote_vault.owner == fund_authority.key())]
+    pub fund_quote_vault: Account<'info, TokenAccount>,
+    #[account(executable, constraint = is_dex_program(dex_program.key))]
+    pub dex_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleHoldingFunds<'info> {
+    pub fund: Account<'info, Fund>,
+    #[account(has_one = fund)]
+    pub holding_market: Account<'info, HoldingMarket>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub market_accounts: Vec<AccountInfo<'info>>, // Market, queues and vaults listed on the holding market
+}
+
+#[derive(Accounts)]
+pub struct CancelHoldingOrder<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(has_one = fund)]
+    pub holding_market: Account<'info, HoldingMarket>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub market_accounts: Vec<AccountInfo<'info>>, // Market, queues and vaults listed on the holding market
+}
+
+/// Account infos for every account a holding's market needs, resolved from a
+/// `HoldingMarket` record so callers can't substitute a different market
+pub struct OpenBookMarket<'a, 'info> {
+    pub dex_program: &'a AccountInfo<'info>,
+    pub market: &'a AccountInfo<'info>,
+    pub open_orders: &'a AccountInfo<'info>,
+    pub request_queue: &'a AccountInfo<'info>,
+    pub event_queue: &'a AccountInfo<'info>,
+    pub bids: &'a AccountInfo<'info>,
+    pub asks: &'a AccountInfo<'info>,
+    pub coin_vault: &'a AccountInfo<'info>,
+    pub pc_vault: &'a AccountInfo<'info>,
+    pub vault_signer: &'a AccountInfo<'info>,
+    pub fund_base_vault: &'a AccountInfo<'info>,
+    pub fund_quote_vault: &'a AccountInfo<'info>,
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+}
+
+impl<'a, 'info> OpenBookMarket<'a, 'info> {
+    pub fn load(
+        holding_market: &HoldingMarket,
+        accounts: &'a [AccountInfo<'info>],
+        fund_authority: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        rent: AccountInfo<'info>,
+    ) -> Result<Self, ProgramError> {
+        Ok(OpenBookMarket {
+            dex_program: find_account_by_key(accounts, &holding_market.dex_program)?,
+            market: find_account_by_key(accounts, &holding_market.market)?,
+            open_orders: find_account_by_key(accounts, &holding_market.open_orders)?,
+            request_queue: find_account_by_key(accounts, &holding_market.request_queue)?,
+            event_queue: find_account_by_key(accounts, &holding_market.event_queue)?,
+            bids: find_account_by_key(accounts, &holding_market.bids)?,
+            asks: find_account_by_key(accounts, &holding_market.asks)?,
+            coin_vault: find_account_by_key(accounts, &holding_market.coin_vault)?,
+            pc_vault: find_account_by_key(accounts, &holding_market.pc_vault)?,
+            vault_signer: find_account_by_key(accounts, &holding_market.vault_signer)?,
+            fund_base_vault: find_account_by_key(accounts, &holding_market.fund_base_vault)?,
+            fund_quote_vault: find_account_by_key(accounts, &holding_market.fund_quote_vault)?,
+            fund_authority,
+            token_program,
+            rent,
+        })
+    }
+}
+
+pub fn is_dex_program(key: &Pubkey) -> bool {
+    *key == openbook_dex::ID || *key == serum_dex_v3::ID
+}
+
+/// Pubkey stored in a DEX market field as four little-endian words
+fn dex_pubkey(words: [u64; 4]) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_exact_mut(8).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Pubkey::new_from_array(bytes)
+}
+
+/// Checks the registration against the market's on-chain state, so a holding can only be
+/// traded on the queues, vaults and lot sizes the DEX itself uses for the market
+fn verify_market_args(
+    market: &AccountInfo,
+    dex_program: &Pubkey,
+    args: &RegisterMarketArgs,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+) -> ProgramResult {
+    let state = DexMarket::load(market, dex_program, false).map_err(|_| ProgramError::InvalidAccountData)?;
+    let vault_signer = gen_vault_signer_key(state.vault_signer_nonce, market.key, dex_program)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    if dex_pubkey(state.own_address) != *market.key
+        || dex_pubkey(state.coin_mint) != *base_mint
+        || dex_pubkey(state.pc_mint) != *quote_mint
+        || dex_pubkey(state.bids) != args.bids
+        || dex_pubkey(state.asks) != args.asks
+        || dex_pubkey(state.req_q) != args.request_queue
+        || dex_pubkey(state.event_q) != args.event_queue
+        || dex_pubkey(state.coin_vault) != args.coin_vault
+        || dex_pubkey(state.pc_vault) != args.pc_vault
+        || vault_signer != args.vault_signer
+        || state.coin_lot_size != args.base_lot_size
+        || state.pc_lot_size != args.quote_lot_size
+    {
+        msg!("Market accounts or lot sizes don't match market {}", market.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Address of the market record for a holding of `fund`
+pub fn holding_market_address(fund: &Pubkey, base_mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[HOLDING_MARKET_SEED, fund.as_ref(), base_mint.as_ref()], program_id).0
+}
+
+pub fn register_market(
+    ctx: Context<RegisterMarket>,
+    args: RegisterMarketArgs,
+    bump: u8,
+    open_orders_bump: u8,
+) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let market_key = ctx.accounts.market.key();
+    let dex_program_key = ctx.accounts.dex_program.key();
+
+    if args.base_lot_size == 0 || args.quote_lot_size == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    verify_market_args(
+        &ctx.accounts.market,
+        &dex_program_key,
+        &args,
+        &ctx.accounts.base_mint.key(),
+        &ctx.accounts.quote_mint.key(),
+    )?;
+
+    // Create the open orders account at its PDA, owned by the DEX
+    let lamports = ctx.accounts.rent.minimum_balance(OPEN_ORDERS_SPACE as usize);
+    let create_ix = system_instruction::create_account(
+        ctx.accounts.owner.key,
+        ctx.accounts.open_orders.key,
+        lamports,
+        OPEN_ORDERS_SPACE,
+        &dex_program_key,
+    );
+    invoke_signed(
+        &create_ix,
+        &[
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.open_orders.clone(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        &[&[OPEN_ORDERS_SEED, fund_key.as_ref(), market_key.as_ref(), &[open_orders_bump]]],
+    )?;
+
+    // The fund authority PDA owns the open orders account
+    let init_ix = dex_instruction::init_open_orders(
+        &dex_program_key,
+        ctx.accounts.open_orders.key,
+        ctx.accounts.fund_authority.key,
+        &market_key,
+        None,
+    )
+    .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let authority_seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    invoke_signed(
+        &init_ix,
+        &[
+            ctx.accounts.open_orders.clone(),
+            ctx.accounts.fund_authority.clone(),
+            ctx.accounts.market.clone(),
+            ctx.accounts.rent.to_account_info(),
+            ctx.accounts.dex_program.clone(),
+        ],
+        &[&authority_seeds[..]],
+    )?;
+
+    let holding_market = &mut ctx.accounts.holding_market;
+    holding_market.fund = fund_key;
+    holding_market.base_mint = ctx.accounts.base_mint.key();
+    holding_market.quote_mint = ctx.accounts.quote_mint.key();
+    holding_market.dex_program = dex_program_key;
+    holding_market.market = market_key;
+    holding_market.bids = args.bids;
+    holding_market.asks = args.asks;
+    holding_market.request_queue = args.request_queue;
+    holding_market.event_queue = args.event_queue;
+    holding_market.coin_vault = args.coin_vault;
+    holding_market.pc_vault = args.pc_vault;
+    holding_market.vault_signer = args.vault_signer;
+    holding_market.open_orders = ctx.accounts.open_orders.key();
+    holding_market.fund_base_vault = ctx.accounts.fund_base_vault.key();
+    holding_market.fund_quote_vault = ctx.accounts.fund_quote_vault.key();
+    holding_market.base_lot_size = args.base_lot_size;
+    holding_market.quote_lot_size = args.quote_lot_size;
+    holding_market.bump = bump;
+    holding_market.open_orders_bump = open_orders_bump;
+
+    msg!("Registered market {} for holding {}", market_key, holding_market.base_mint);
+    Ok(())
+}
+
+/// Limit price in the market's lot units for trading `base_amount` against `quote_amount`, both native
+pub fn price_lots(holding_market: &HoldingMarket, quote_amount: u64, base_amount: u64, round_up: bool) -> Result<u64, ProgramError> {
+    let numerator = quote_amount as u128 * holding_market.base_lot_size as u128;
+    let denominator = base_amount as u128 * holding_market.quote_lot_size as u128;
+    if denominator == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let lots = if round_up {
+        (numerator + denominator - 1) / denominator
+    } else {
+        numerator / denominator
+    };
+    u64::try_from(lots).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Limits of an immediate-or-cancel order, with base and quote amounts native
+pub struct IocOrder {
+    pub max_base_qty: u64,
+    pub max_quote_qty: u64, // Including the taker fee on bids
+    pub limit_price_lots: u64,
+}
+
+/// Order trading `amount_in` for at least `minimum_amount_out` after the taker fee. Bids spend at
+/// most `amount_in` quote and buy any number of lots, so the minimum is rounded up to whole lots;
+/// asks sell the whole lots in `amount_in`. The limit is the worst price at which that fill still
+/// meets the minimum. The DEX rounds its fee up, so one unit of quote is set aside for it.
+pub fn ioc_order(holding_market: &HoldingMarket, side: Side, amount_in: u64, minimum_amount_out: u64) -> Result<IocOrder, ProgramError> {
+    let base_lot_size = holding_market.base_lot_size as u128;
+    let fee_denominator = BPS_DENOMINATOR as u128;
+    let net_of_fee = fee_denominator - OPENBOOK_TAKER_FEE_BPS as u128;
+
+    match side {
+        Side::Bid => {
+            let spendable = (amount_in as u128 * net_of_fee / fee_denominator).saturating_sub(1);
+            let minimum_lots = ((minimum_amount_out as u128 + base_lot_size - 1) / base_lot_size).max(1);
+            let minimum_base = u64::try_from(minimum_lots * base_lot_size).map_err(|_| ProgramError::InvalidArgument)?;
+            Ok(IocOrder {
+                max_base_qty: u64::MAX,
+                max_quote_qty: amount_in,
+                limit_price_lots: price_lots(holding_market, spendable as u64, minimum_base, false)?,
+            })
+        }
+        Side::Ask => {
+            let sold_base = amount_in as u128 / base_lot_size * base_lot_size;
+            if sold_base == 0 {
+                return Err(SaturnFundError::OrderBelowLotSize.into());
+            }
+            let gross_quote = ((minimum_amount_out as u128 + 1) * fee_denominator + net_of_fee - 1) / net_of_fee;
+            let gross_quote = u64::try_from(gross_quote).map_err(|_| ProgramError::InvalidArgument)?;
+            Ok(IocOrder {
+                max_base_qty: sold_base as u64,
+                max_quote_qty: u64::MAX,
+                limit_price_lots: price_lots(holding_market, gross_quote, sold_base as u64, true)?,
+            })
+        }
+    }
+}
+
+/// Places an immediate-or-cancel order trading at most `max_base_qty` base and `max_quote_qty` quote
+/// (both native) at no worse than `limit_price_lots`, paying from the fund's quote vault on bids
+/// and its base vault on asks. Fails with `OrderBelowLotSize` when the order rounds to nothing.
+pub fn place_order(
+    market: &OpenBookMarket,
+    holding_market: &HoldingMarket,
+    side: Side,
+    max_base_qty: u64,
+    max_quote_qty: u64,
+    limit_price_lots: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let (limit_price_lots, max_coin_qty, max_native_pc_qty) = match (
+        NonZeroU64::new(limit_price_lots),
+        NonZeroU64::new(max_base_qty / holding_market.base_lot_size),
+        NonZeroU64::new(max_quote_qty),
+    ) {
+        (Some(price), Some(coin), Some(pc)) => (price, coin, pc),
+        _ => {
+            msg!("Order below the market's lot sizes.");
+            return Err(SaturnFundError::OrderBelowLotSize.into());
+        }
+    };
+
+    let order_payer = match side {
+        Side::Bid => market.fund_quote_vault,
+        Side::Ask => market.fund_base_vault,
+    };
+
+    let ix = dex_instruction::new_order(
+        market.market.key,
+        market.open_orders.key,
+        market.request_queue.key,
+        market.event_queue.key,
+        market.bids.key,
+        market.asks.key,
+        order_payer.key,
+        market.fund_authority.key,
+        market.coin_vault.key,
+        market.pc_vault.key,
+        &token::ID,
+        &sysvar::rent::ID,
+        None,
+        market.dex_program.key,
+        side,
+        limit_price_lots,
+        max_coin_qty,
+        OrderType::ImmediateOrCancel,
+        0,
+        SelfTradeBehavior::DecrementTake,
+        u16::MAX,
+        max_native_pc_qty,
+    )
+    .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    msg!("Placing {:?} order on OpenBook...", side);
+    invoke_signed(
+        &ix,
+        &[
+            market.market.clone(),
+            market.open_orders.clone(),
+            market.request_queue.clone(),
+            market.event_queue.clone(),
+            market.bids.clone(),
+            market.asks.clone(),
+            order_payer.clone(),
+            market.fund_authority.clone(),
+            market.coin_vault.clone(),
+            market.pc_vault.clone(),
+            market.token_program.clone(),
+            market.rent.clone(),
+            market.dex_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Moves free balances from the fund's open orders account back into its vaults
+pub fn settle_funds(market: &OpenBookMarket, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let ix = dex_instruction::settle_funds(
+        market.dex_program.key,
+        market.market.key,
+        &token::ID,
+        market.open_orders.key,
+        market.fund_authority.key,
+        market.coin_vault.key,
+        market.fund_base_vault.key,
+        market.pc_vault.key,
+        market.fund_quote_vault.key,
+        None,
+        market.vault_signer.key,
+    )
+    .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    invoke_signed(
+        &ix,
+        &[
+            market.market.clone(),
+            market.open_orders.clone(),
+            market.fund_authority.clone(),
+            market.coin_vault.clone(),
+            market.fund_base_vault.clone(),
+            market.pc_vault.clone(),
+            market.fund_quote_vault.clone(),
+            market.vault_signer.clone(),
+            market.token_program.clone(),
+            market.dex_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn settle_holding_funds(ctx: Context<SettleHoldingFunds>) -> ProgramResult {
+    let market = OpenBookMarket::load(
+        &ctx.accounts.holding_market,
+        &ctx.accounts.market_accounts,
+        ctx.accounts.fund_authority.clone(),
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.rent.to_account_info(),
+    )?;
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    settle_funds(&market, &[&seeds[..]])?;
+
+    msg!("Settled funds for market {}", ctx.accounts.holding_market.market);
+    Ok(())
+}
+
+/// Cancels a resting order of the fund and settles the released funds
+pub fn cancel_holding_order(ctx: Context<CancelHoldingOrder>, is_bid: bool, order_id: u128) -> ProgramResult {
+    let market = OpenBookMarket::load(
+        &ctx.accounts.holding_market,
+        &ctx.accounts.market_accounts,
+        ctx.accounts.fund_authority.clone(),
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.rent.to_account_info(),
+    )?;
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    let side = if is_bid { Side::Bid } else { Side::Ask };
+    let ix = dex_instruction::cancel_order(
+        market.dex_program.key,
+        market.market.key,
+        market.bids.key,
+        market.asks.key,
+        market.open_orders.key,
+        market.fund_authority.key,
+        market.event_queue.key,
+        side,
+        order_id,
+    )
+    .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    invoke_signed(
+        &ix,
+        &[
+            market.market.clone(),
+            market.bids.clone(),
+            market.asks.clone(),
+            market.open_orders.clone(),
+            market.fund_authority.clone(),
+            market.event_queue.clone(),
+            market.dex_program.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    settle_funds(&market, &[&seeds[..]])?;
+
+    msg!("Cancelled order {} on market {}", order_id, ctx.accounts.holding_market.market);
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    sysvar,
+};
+use anchor_spl::token;
+use crate::instructions::collect_liquidity_pool_rewards::{lock_retained_rewards, pay_reward_recipients};
+use crate::instructions::raydium_integration::token_account_amount;
+use crate::state::allocation::{Allocation, MAX_LIQUIDITY_POOLS};
+use crate::state::fund::{Fund, StakedLiquidity, FUND_AUTHORITY_SEED};
+
+/// Raydium staking (farm v3) program
+pub mod raydium_farm_v3 {
+    anchor_lang::declare_id!("EhhTKczWMGQt46ynNeRX1WfeagwwJd7ufHvCDjRxjo5Q");
+}
+
+// Raydium farm instruction tags. A deposit of zero only harvests pending rewards.
+const FARM_DEPOSIT_TAG: u8 = 1;
+const FARM_WITHDRAW_TAG: u8 = 2;
+
+#[derive(Accounts)]
+pub struct RaydiumFarm<'info> {
+    #[account(address = raydium_farm_v3::ID)]
+    pub farm_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub farm: AccountInfo<'info>,
+    pub farm_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub staker_info: AccountInfo<'info>, // Farm's record of the fund's stake
+    #[account(mut)]
+    pub farm_lp_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub farm_reward_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub fund_lp_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub fund_reward_vault: AccountInfo<'info>, // Harvested rewards are paid here
+    pub fund_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, token::Token>,
+}
+
+fn farm_instruction(accounts: &RaydiumFarm, tag: u8, amount: u64) -> Instruction {
+    let mut data = Vec::with_capacity(9);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new(*accounts.farm.key, false),
+        AccountMeta::new_readonly(*accounts.farm_authority.key, false),
+        AccountMeta::new(*accounts.staker_info.key, false),
+        AccountMeta::new_readonly(*accounts.fund_authority.key, true),
+        AccountMeta::new(*accounts.fund_lp_vault.key, false),
+        AccountMeta::new(*accounts.farm_lp_vault.key, false),
+        AccountMeta::new(*accounts.fund_reward_vault.key, false),
+        AccountMeta::new(*accounts.farm_reward_vault.key, false),
+        AccountMeta::new_readonly(sysvar::clock::ID, false),
+        AccountMeta::new_readonly(accounts.token_program.key(), false),
+    ];
+
+    Instruction {
+        program_id: raydium_farm_v3::ID,
+        accounts,
+        data,
+    }
+}
+
+fn invoke_farm(accounts: &RaydiumFarm, tag: u8, amount: u64, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let ix = farm_instruction(accounts, tag, amount);
+
+    invoke_signed(
+        &ix,
+        &[
+            accounts.farm.clone(),
+            accounts.farm_authority.clone(),
+            accounts.staker_info.clone(),
+            accounts.fund_authority.clone(),
+            accounts.fund_lp_vault.clone(),
+            accounts.farm_lp_vault.clone(),
+            accounts.fund_reward_vault.clone(),
+            accounts.farm_reward_vault.clone(),
+            accounts.clock.to_account_info(),
+            accounts.token_program.to_account_info(),
+            accounts.farm_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Harvests pending farm rewards for the fund's staked LP tokens and returns the amount
+/// actually received, measured on the fund's reward vault
+pub fn harvest_farm_rewards(accounts: &RaydiumFarm, signer_seeds: &[&[&[u8]]]) -> Result<u64, ProgramError> {
+    let balance_before = token_account_amount(&accounts.fund_reward_vault)?;
+
+    msg!("Harvesting Raydium farm rewards...");
+    invoke_farm(accounts, FARM_DEPOSIT_TAG, 0, signer_seeds)?;
+
+    let balance_after = token_account_amount(&accounts.fund_reward_vault)?;
+    let harvested = balance_after
+        .checked_sub(balance_before)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    msg!("Harvested {} reward tokens.", harvested);
+    Ok(harvested)
+}
+
+#[derive(Accounts)]
+pub struct ManageRaydiumFarm<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+        constraint = fund_authority.key() == farm.fund_authority.key(),
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub farm: RaydiumFarm<'info>,
+    pub reward_price_oracle: AccountInfo<'info>, // Prices the reward token when its vault is a holding vault
+    pub token_accounts: Vec<AccountInfo<'info>>, // Reward recipient accounts
+}
+
+/// Runs a farm deposit or withdraw. The farm pays out pending rewards on both, so whatever
+/// arrives in the reward vault is split and vested like a regular harvest.
+fn invoke_farm_with_harvest(ctx: &mut Context<ManageRaydiumFarm>, tag: u8, amount: u64) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    let rewards_before = token_account_amount(&ctx.accounts.farm.fund_reward_vault)?;
+    invoke_farm(&ctx.accounts.farm, tag, amount, &[&seeds[..]])?;
+    let rewards = token_account_amount(&ctx.accounts.farm.fund_reward_vault)?
+        .checked_sub(rewards_before)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    if rewards == 0 {
+        return Ok(());
+    }
+
+    let reinvested = pay_reward_recipients(
+        &ctx.accounts.fund,
+        &ctx.accounts.farm.fund_reward_vault,
+        &ctx.accounts.fund_authority,
+        &ctx.accounts.farm.token_program.to_account_info(),
+        &ctx.accounts.token_accounts,
+        rewards,
+        &[&seeds[..]],
+    )?;
+    let now = ctx.accounts.farm.clock.unix_timestamp;
+    let reward_vault = ctx.accounts.farm.fund_reward_vault.key();
+    lock_retained_rewards(
+        &mut ctx.accounts.fund,
+        &ctx.accounts.allocation_pda,
+        &reward_vault,
+        &ctx.accounts.reward_price_oracle,
+        reinvested,
+        now,
+    )?;
+
+    msg!("Rewards harvested: {}, reinvested into the fund: {}", rewards, reinvested);
+    Ok(())
+}
+
+/// Stakes `amount` LP tokens from the fund's LP vault into the farm configured for the pool at `pool_index`
+pub fn stake_lp(mut ctx: Context<ManageRaydiumFarm>, pool_index: u8, amount: u64) -> ProgramResult {
+    let lp_mint = check_configured_farm(&ctx.accounts.allocation_pda, &ctx.accounts.farm, pool_index)?;
+
+    let balance_before = token_account_amount(&ctx.accounts.farm.fund_lp_vault)?;
+    invoke_farm_with_harvest(&mut ctx, FARM_DEPOSIT_TAG, amount)?;
+    let balance_after = token_account_amount(&ctx.accounts.farm.fund_lp_vault)?;
+
+    // Track what actually left the vault rather than the requested amount
+    let staked = balance_before
+        .checked_sub(balance_after)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let farm_key = ctx.accounts.farm.farm.key();
+    let position = staked_position(&mut ctx.accounts.fund, lp_mint, farm_key)?;
+    position.amount = position.amount
+        .checked_add(staked)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    msg!("Staked {} LP tokens in farm {}", staked, farm_key);
+    Ok(())
+}
+
+/// Withdraws `amount` staked LP tokens from the farm back into the fund's LP vault
+pub fn unstake_lp(mut ctx: Context<ManageRaydiumFarm>, pool_index: u8, amount: u64) -> ProgramResult {
+    let lp_mint = check_configured_farm(&ctx.accounts.allocation_pda, &ctx.accounts.farm, pool_index)?;
+
+    let farm_key = ctx.accounts.farm.farm.key();
+    let staked = ctx.accounts.fund.staked_lp.iter()
+        .find(|staked| staked.lp_mint == lp_mint && staked.farm == farm_key)
+        .map_or(0, |staked| staked.amount);
+    if staked < amount {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    let balance_before = token_account_amount(&ctx.accounts.farm.fund_lp_vault)?;
+    invoke_farm_with_harvest(&mut ctx, FARM_WITHDRAW_TAG, amount)?;
+    let balance_after = token_account_amount(&ctx.accounts.farm.fund_lp_vault)?;
+
+    let unstaked = balance_after
+        .checked_sub(balance_before)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let position = staked_position(&mut ctx.accounts.fund, lp_mint, farm_key)?;
+    position.amount = position.amount
+        .checked_sub(unstaked)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    ctx.accounts.fund.staked_lp.retain(|staked| staked.amount > 0);
+
+    msg!("Unstaked {} LP tokens from farm {}", unstaked, farm_key);
+    Ok(())
+}
+
+/// Checks the farm and LP vault against the allocation and returns the pool's LP mint
+pub fn check_configured_farm(allocation: &Allocation, farm: &RaydiumFarm, pool_index: u8) -> Result<Pubkey, ProgramError> {
+    let pool_config = allocation.liquidity_pools
+        .get(pool_index as usize)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    if pool_config.farm == Pubkey::default()
+        || *farm.farm.key != pool_config.farm
+        || *farm.fund_lp_vault.key != pool_config.lp_vault
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok(pool_config.lp_mint)
+}
+
+fn staked_position(fund: &mut Fund, lp_mint: Pubkey, farm: Pubkey) -> Result<&mut StakedLiquidity, ProgramError> {
+    let index = match fund.staked_lp.iter().position(|staked| staked.lp_mint == lp_mint && staked.farm == farm) {
+        Some(index) => index,
+        None => {
+            // The fund account only has room for one position per liquidity pool
+            if fund.staked_lp.len() >= MAX_LIQUIDITY_POOLS {
+                return Err(ProgramError::InvalidArgument);
+            }
+            fund.staked_lp.push(StakedLiquidity { lp_mint, farm, amount: 0 });
+            fund.staked_lp.len() - 1
+        }
+    };
+    Ok(&mut fund.staked_lp[index])
+}
@@ -1,21 +1,37 @@
 use anchor_lang::prelude::*;
 use solana_program::{
-    program::invoke,
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed},
     account_info::AccountInfo,
     pubkey::Pubkey,
     sysvar::{clock::Clock, Sysvar},
 };
-use anchor_spl::token::{self, TokenAccount, Transfer};
+use anchor_spl::token::{self, Mint, TokenAccount, Transfer};
+use crate::error::SaturnFundError;
+use crate::instructions::manage_holdings::{find_account_by_key, get_holdings_value};
+use crate::instructions::trade_limits::{apply_slippage, verify_amount_received};
+use crate::state::allocation::{Allocation, LiquidityPoolAllocation};
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
 
-// Function to fetch the current market price from Raydium's oracle
+// Function to fetch the current market price from Raydium's oracle.
+// The oracle must belong to the fund's oracle program and be no older than `max_oracle_age`.
 pub fn get_current_market_price_from_raydium(
+    fund: &Fund,
     oracle_account: &AccountInfo
 ) -> Result<u64, ProgramError> {
-    //  Fetch and decode the price data from the oracle account
- 
+    if *oracle_account.owner != fund.oracle_program {
+        return Err(ProgramError::IllegalOwner);
+    }
+
     let data = oracle_account.try_borrow_data()?;
     let price_data = decode_price_data(&data)?;
 
+    let now = Clock::get()?.unix_timestamp.max(0) as u64;
+    if now.saturating_sub(price_data.timestamp) > fund.max_oracle_age.max(0) as u64 {
+        msg!("Oracle {} last updated at {}", oracle_account.key, price_data.timestamp);
+        return Err(SaturnFundError::StaleOraclePrice.into());
+    }
+
     Ok(price_data.price)
 }
 
@@ -40,93 +56,683 @@ struct PriceData {
     timestamp: u64,  // Added timestamp to the PriceData struct
 }
 
+/// Raydium AMM v4 (liquidity pool v4) program
+pub mod raydium_amm_v4 {
+    anchor_lang::declare_id!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+}
+
+// Raydium AMM v4 instruction tags
+const SWAP_BASE_IN_TAG: u8 = 9;
+
+// Offsets of the accounts recorded in a Raydium AMM v4 pool state
+pub(crate) const AMM_COIN_VAULT_OFFSET: usize = 336;
+pub(crate) const AMM_PC_VAULT_OFFSET: usize = 368;
+pub(crate) const AMM_OPEN_ORDERS_OFFSET: usize = 496;
+pub(crate) const AMM_MARKET_OFFSET: usize = 528;
+pub(crate) const AMM_SERUM_PROGRAM_OFFSET: usize = 560;
+pub(crate) const AMM_TARGET_ORDERS_OFFSET: usize = 592;
+pub(crate) const AMM_STATE_LEN: usize = 752;
+
+#[derive(Accounts)]
+pub struct SwapViaRaydium<'info> {
+    pub amm_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm: AccountInfo<'info>,
+    pub amm_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm_open_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm_target_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub pool_coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub pool_pc_vault: AccountInfo<'info>,
+    pub serum_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_market: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_bids: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_asks: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_event_queue: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_pc_vault: AccountInfo<'info>,
+    pub serum_vault_signer: AccountInfo<'info>,
+    #[account(mut)]
+    pub source_token_account: AccountInfo<'info>, // Fund vault the input is taken from
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>, // Fund vault the output is paid into
+    pub source_owner: AccountInfo<'info>, // Fund PDA owning the source vault
+    pub token_program: Program<'info, token::Token>,
+}
+
+/// Addresses a Raydium AMM v4 swap touches, in the order the AMM program expects them
+pub struct SwapBaseInKeys {
+    pub token_program: Pubkey,
+    pub amm: Pubkey,
+    pub amm_authority: Pubkey,
+    pub amm_open_orders: Pubkey,
+    pub amm_target_orders: Pubkey,
+    pub pool_coin_vault: Pubkey,
+    pub pool_pc_vault: Pubkey,
+    pub serum_program: Pubkey,
+    pub serum_market: Pubkey,
+    pub serum_bids: Pubkey,
+    pub serum_asks: Pubkey,
+    pub serum_event_queue: Pubkey,
+    pub serum_coin_vault: Pubkey,
+    pub serum_pc_vault: Pubkey,
+    pub serum_vault_signer: Pubkey,
+    pub source_token_account: Pubkey,
+    pub destination_token_account: Pubkey,
+    pub source_owner: Pubkey,
+}
+
+impl SwapBaseInKeys {
+    pub fn from_accounts(accounts: &SwapViaRaydium) -> Self {
+        SwapBaseInKeys {
+            token_program: accounts.token_program.key(),
+            amm: *accounts.amm.key,
+            amm_authority: *accounts.amm_authority.key,
+            amm_open_orders: *accounts.amm_open_orders.key,
+            amm_target_orders: *accounts.amm_target_orders.key,
+            pool_coin_vault: *accounts.pool_coin_vault.key,
+            pool_pc_vault: *accounts.pool_pc_vault.key,
+            serum_program: *accounts.serum_program.key,
+            serum_market: *accounts.serum_market.key,
+            serum_bids: *accounts.serum_bids.key,
+            serum_asks: *accounts.serum_asks.key,
+            serum_event_queue: *accounts.serum_event_queue.key,
+            serum_coin_vault: *accounts.serum_coin_vault.key,
+            serum_pc_vault: *accounts.serum_pc_vault.key,
+            serum_vault_signer: *accounts.serum_vault_signer.key,
+            source_token_account: *accounts.source_token_account.key,
+            destination_token_account: *accounts.destination_token_account.key,
+            source_owner: *accounts.source_owner.key,
+        }
+    }
+}
+
+/// Builds a Raydium AMM v4 `swap_base_in` instruction.
+/// Data layout is the tag byte followed by `amount_in` and `minimum_amount_out` as little-endian u64s.
+pub fn swap_base_in_instruction(
+    keys: &SwapBaseInKeys,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(17);
+    data.push(SWAP_BASE_IN_TAG);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    // Account order is fixed by the AMM program
+    let accounts = vec![
+        AccountMeta::new_readonly(keys.token_program, false),
+        AccountMeta::new(keys.amm, false),
+        AccountMeta::new_readonly(keys.amm_authority, false),
+        AccountMeta::new(keys.amm_open_orders, false),
+        AccountMeta::new(keys.amm_target_orders, false),
+        AccountMeta::new(keys.pool_coin_vault, false),
+        AccountMeta::new(keys.pool_pc_vault, false),
+        AccountMeta::new_readonly(keys.serum_program, false),
+        AccountMeta::new(keys.serum_market, false),
+        AccountMeta::new(keys.serum_bids, false),
+        AccountMeta::new(keys.serum_asks, false),
+        AccountMeta::new(keys.serum_event_queue, false),
+        AccountMeta::new(keys.serum_coin_vault, false),
+        AccountMeta::new(keys.serum_pc_vault, false),
+        AccountMeta::new_readonly(keys.serum_vault_signer, false),
+        AccountMeta::new(keys.source_token_account, false),
+        AccountMeta::new(keys.destination_token_account, false),
+        AccountMeta::new_readonly(keys.source_owner, true),
+    ];
+
+    Instruction {
+        program_id: raydium_amm_v4::ID,
+        accounts,
+        data,
+    }
+}
+
 pub fn swap_via_raydium(
-    accounts: &Context<SwapViaRaydium>, 
-    swap_amount: u64
+    accounts: &SwapViaRaydium,
+    swap_amount: u64,
+    minimum_amount_out: u64,
+    signer_seeds: &[&[&[u8]]], // Seeds of the fund PDA that owns the source vault
 ) -> ProgramResult {
-    // Assuming `accounts` includes all necessary accounts like user's SOL account, 
-    // destination token account, Raydium's program account, etc.
-
-    let ix = spl_token_swap::instruction::swap(
-        &raydium_program_id, // Raydium swap program ID
-        &accounts.user_sol_account.key, // User's SOL account
-        &accounts.pool_sol_account.key, // Raydium pool SOL account
-        &accounts.pool_token_account.key, // Raydium pool token account
-        &accounts.user_token_account.key, // Destination token account
-        &accounts.pool_mint.key, // Pool mint account
-        &accounts.fee_account.key, // Fee account (if applicable)
-        None, // Host fee account (if applicable)
-        swap_amount,
-        0 // Minimum amount of tokens to receive (set to 0 for simplicity)
-    )?;
+    if *accounts.amm_program.key != raydium_amm_v4::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let ix = swap_base_in_instruction(&SwapBaseInKeys::from_accounts(accounts), swap_amount, minimum_amount_out);
+
+    let balance_before = token_account_amount(&accounts.destination_token_account)?;
 
     msg!("Calling the Raydium swap program...");
-    invoke(
+    invoke_signed(
         &ix,
         &[
-            accounts.user_sol_account.clone(),
-            accounts.pool_sol_account.clone(),
-            accounts.pool_token_account.clone(),
-            accounts.user_token_account.clone(),
-            accounts.pool_mint.clone(),
-            accounts.fee_account.clone(),
-            accounts.token_program.clone(), // SPL Token program
-            accounts.system_program.clone(), // Solana System program
+            accounts.token_program.to_account_info(),
+            accounts.amm.clone(),
+            accounts.amm_authority.clone(),
+            accounts.amm_open_orders.clone(),
+            accounts.amm_target_orders.clone(),
+            accounts.pool_coin_vault.clone(),
+            accounts.pool_pc_vault.clone(),
+            accounts.serum_program.clone(),
+            accounts.serum_market.clone(),
+            accounts.serum_bids.clone(),
+            accounts.serum_asks.clone(),
+            accounts.serum_event_queue.clone(),
+            accounts.serum_coin_vault.clone(),
+            accounts.serum_pc_vault.clone(),
+            accounts.serum_vault_signer.clone(),
+            accounts.source_token_account.clone(),
+            accounts.destination_token_account.clone(),
+            accounts.source_owner.clone(),
+            accounts.amm_program.clone(),
         ],
+        signer_seeds,
     )?;
 
+    // Don't rely on the pool alone to enforce the minimum
+    let balance_after = token_account_amount(&accounts.destination_token_account)?;
+    verify_amount_received(balance_before, balance_after, minimum_amount_out)?;
+
     msg!("Swap completed successfully.");
     Ok(())
 }
 
+pub fn token_account_amount(account: &AccountInfo) -> Result<u64, ProgramError> {
+    let data = account.try_borrow_data()?;
+    let token_account = TokenAccount::try_deserialize(&mut &data[..])?;
+    Ok(token_account.amount)
+}
+
+#[derive(Accounts)]
+pub struct RaydiumLiquidity<'info> {
+    pub amm_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm: AccountInfo<'info>,
+    pub amm_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm_open_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub amm_target_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub pool_coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub pool_pc_vault: AccountInfo<'info>,
+    pub serum_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_market: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_pc_vault: AccountInfo<'info>,
+    pub serum_vault_signer: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_event_queue: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_bids: AccountInfo<'info>,
+    #[account(mut)]
+    pub serum_asks: AccountInfo<'info>,
+    #[account(mut)]
+    pub fund_coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub fund_pc_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub fund_lp_vault: AccountInfo<'info>, // LP tokens are held here, owned by the fund PDA
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+}
+
+#[derive(Accounts)]
+pub struct ManageRaydiumLiquidity<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+        constraint = fund_authority.key() == pool.fund_authority.key(),
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub pool: RaydiumLiquidity<'info>,
+}
+
+// Raydium AMM v4 liquidity instruction tags
+const DEPOSIT_TAG: u8 = 3;
+const WITHDRAW_TAG: u8 = 4;
+
+/// Current pool reserves as (coin, pc) held in the pool vaults
+pub fn get_pool_reserves(accounts: &RaydiumLiquidity) -> Result<(u64, u64), ProgramError> {
+    let coin_reserve = token_account_amount(&accounts.pool_coin_vault)?;
+    let pc_reserve = token_account_amount(&accounts.pool_pc_vault)?;
+    Ok((coin_reserve, pc_reserve))
+}
+
+// Side of a deposit Raydium sizes the other side from
+const BASE_SIDE_COIN: u64 = 0;
+const BASE_SIDE_PC: u64 = 1;
+
+/// Expected (coin, pc) deposit at the ratio of the pool vaults, and the side Raydium should size
+/// from. Raydium also counts open-orders balances and PnL, so these are estimates only.
+pub fn balanced_deposit_amounts(
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    coin_reserve: u64,
+    pc_reserve: u64,
+) -> Result<(u64, u64, u64), ProgramError> {
+    if coin_reserve == 0 || pc_reserve == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let pc_for_max_coin = max_coin_amount as u128 * pc_reserve as u128 / coin_reserve as u128;
+    if pc_for_max_coin <= max_pc_amount as u128 {
+        return Ok((max_coin_amount, pc_for_max_coin as u64, BASE_SIDE_COIN));
+    }
+
+    let coin_for_max_pc = max_pc_amount as u128 * coin_reserve as u128 / pc_reserve as u128;
+    Ok((coin_for_max_pc as u64, max_pc_amount, BASE_SIDE_PC))
+}
+
+fn deposit_instruction(accounts: &RaydiumLiquidity, max_coin_amount: u64, max_pc_amount: u64, base_side: u64) -> Instruction {
+    let mut data = Vec::with_capacity(25);
+    data.push(DEPOSIT_TAG);
+    data.extend_from_slice(&max_coin_amount.to_le_bytes());
+    data.extend_from_slice(&max_pc_amount.to_le_bytes());
+    data.extend_from_slice(&base_side.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new_readonly(accounts.token_program.key(), false),
+        AccountMeta::new(*accounts.amm.key, false),
+        AccountMeta::new_readonly(*accounts.amm_authority.key, false),
+        AccountMeta::new_readonly(*accounts.amm_open_orders.key, false),
+        AccountMeta::new(*accounts.amm_target_orders.key, false),
+        AccountMeta::new(*accounts.lp_mint.key, false),
+        AccountMeta::new(*accounts.pool_coin_vault.key, false),
+        AccountMeta::new(*accounts.pool_pc_vault.key, false),
+        AccountMeta::new_readonly(*accounts.serum_market.key, false),
+        AccountMeta::new(*accounts.fund_coin_vault.key, false),
+        AccountMeta::new(*accounts.fund_pc_vault.key, false),
+        AccountMeta::new(*accounts.fund_lp_vault.key, false),
+        AccountMeta::new_readonly(*accounts.fund_authority.key, true),
+        AccountMeta::new_readonly(*accounts.serum_event_queue.key, false),
+    ];
+
+    Instruction {
+        program_id: raydium_amm_v4::ID,
+        accounts,
+        data,
+    }
+}
+
+fn withdraw_instruction(accounts: &RaydiumLiquidity, lp_amount: u64) -> Instruction {
+    let mut data = Vec::with_capacity(9);
+    data.push(WITHDRAW_TAG);
+    data.extend_from_slice(&lp_amount.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new_readonly(accounts.token_program.key(), false),
+        AccountMeta::new(*accounts.amm.key, false),
+        AccountMeta::new_readonly(*accounts.amm_authority.key, false),
+        AccountMeta::new(*accounts.amm_open_orders.key, false),
+        AccountMeta::new(*accounts.amm_target_orders.key, false),
+        AccountMeta::new(*accounts.lp_mint.key, false),
+        AccountMeta::new(*accounts.pool_coin_vault.key, false),
+        AccountMeta::new(*accounts.pool_pc_vault.key, false),
+        AccountMeta::new_readonly(*accounts.serum_program.key, false),
+        AccountMeta::new(*accounts.serum_market.key, false),
+        AccountMeta::new(*accounts.serum_coin_vault.key, false),
+        AccountMeta::new(*accounts.serum_pc_vault.key, false),
+        AccountMeta::new_readonly(*accounts.serum_vault_signer.key, false),
+        AccountMeta::new(*accounts.fund_lp_vault.key, false),
+        AccountMeta::new(*accounts.fund_coin_vault.key, false),
+        AccountMeta::new(*accounts.fund_pc_vault.key, false),
+        AccountMeta::new_readonly(*accounts.fund_authority.key, true),
+        AccountMeta::new(*accounts.serum_event_queue.key, false),
+        AccountMeta::new(*accounts.serum_bids.key, false),
+        AccountMeta::new(*accounts.serum_asks.key, false),
+    ];
+
+    Instruction {
+        program_id: raydium_amm_v4::ID,
+        accounts,
+        data,
+    }
+}
+
+fn liquidity_account_infos<'info>(accounts: &RaydiumLiquidity<'info>) -> Vec<AccountInfo<'info>> {
+    vec![
+        accounts.token_program.to_account_info(),
+        accounts.amm.clone(),
+        accounts.amm_authority.clone(),
+        accounts.amm_open_orders.clone(),
+        accounts.amm_target_orders.clone(),
+        accounts.lp_mint.clone(),
+        accounts.pool_coin_vault.clone(),
+        accounts.pool_pc_vault.clone(),
+        accounts.serum_program.clone(),
+        accounts.serum_market.clone(),
+        accounts.serum_coin_vault.clone(),
+        accounts.serum_pc_vault.clone(),
+        accounts.serum_vault_signer.clone(),
+        accounts.serum_event_queue.clone(),
+        accounts.serum_bids.clone(),
+        accounts.serum_asks.clone(),
+        accounts.fund_coin_vault.clone(),
+        accounts.fund_pc_vault.clone(),
+        accounts.fund_lp_vault.clone(),
+        accounts.fund_authority.clone(),
+        accounts.amm_program.clone(),
+    ]
+}
+
+/// Deposits a balanced coin/pc pair within the given maximums and fails with
+/// `SlippageExceeded` if fewer than `min_lp_out` LP tokens arrive. Raydium sizes the
+/// deposit itself, so the maximums are passed through as its limits.
 pub fn increase_liquidity_on_raydium(
-    accounts: &Context<IncreaseLiquidityOnRaydium>, 
-    liquidity_amount: u64,
-    minimum_token_b_amount: u64
+    accounts: &RaydiumLiquidity,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    min_lp_out: u64,
+    signer_seeds: &[&[&[u8]]],
 ) -> ProgramResult {
-    // Assuming `accounts` includes all necessary accounts like user's token accounts, 
-    // Raydium's pool accounts, etc.
-
-    let ix = raydium_sdk::instruction::add_liquidity(
-        &raydium_program_id, // Raydium liquidity program ID
-        &accounts.user_token_a_account.key, // User's token A account
-        &accounts.user_token_b_account.key, // User's token B account
-        &accounts.pool_token_a_account.key, // Raydium pool token A account
-        &accounts.pool_token_b_account.key, // Raydium pool token B account
-        &accounts.pool_lp_token_account.key, // Raydium pool LP token account
-        &accounts.user_lp_token_account.key, // User's LP token account
-        liquidity_amount,
-        minimum_token_b_amount
-    )?;
+    if *accounts.amm_program.key != raydium_amm_v4::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
-    msg!("Calling the Raydium add liquidity program...");
-    invoke(
-        &ix,
-        &[
-            accounts.user_token_a_account.clone(),
-            accounts.user_token_b_account.clone(),
-            accounts.pool_token_a_account.clone(),
-            accounts.pool_token_b_account.clone(),
-            accounts.pool_lp_token_account.clone(),
-            accounts.user_lp_token_account.clone(),
-            accounts.token_program.clone(), // SPL Token program
-            accounts.system_program.clone(), // Solana System program
-        ],
-    )?;
+    let (coin_reserve, pc_reserve) = get_pool_reserves(accounts)?;
+    let (coin_amount, pc_amount, base_side) = balanced_deposit_amounts(max_coin_amount, max_pc_amount, coin_reserve, pc_reserve)?;
+
+    let ix = deposit_instruction(accounts, max_coin_amount, max_pc_amount, base_side);
+    let lp_before = token_account_amount(&accounts.fund_lp_vault)?;
+
+    msg!("Depositing about {} coin / {} pc into Raydium...", coin_amount, pc_amount);
+    invoke_signed(&ix, &liquidity_account_infos(accounts), signer_seeds)?;
+
+    let lp_after = token_account_amount(&accounts.fund_lp_vault)?;
+    verify_amount_received(lp_before, lp_after, min_lp_out)?;
 
     msg!("Liquidity addition completed successfully.");
     Ok(())
 }
 
-pub fn ensure_liquidity_representation(ctx: Context<IncreaseLiquidityOnRaydium>, target_percentage: u64) -> ProgramResult {
-    let total_fund_value = get_total_fund_value(&ctx)?;
-    let required_liquidity_value = total_fund_value * target_percentage / 100;
+/// Burns `lp_amount` LP tokens from the fund's LP vault and checks both sides
+/// of the withdrawal against the given minimums
+pub fn decrease_liquidity_on_raydium(
+    accounts: &RaydiumLiquidity,
+    lp_amount: u64,
+    min_coin_out: u64,
+    min_pc_out: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    if *accounts.amm_program.key != raydium_amm_v4::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
-    let current_liquidity_value = get_current_liquidity_value(&ctx)?;
-    if current_liquidity_value < required_liquidity_value {
-        let difference = required_liquidity_value - current_liquidity_value;
-        increase_liquidity_on_raydium(ctx, difference)?;
+    let ix = withdraw_instruction(accounts, lp_amount);
+    let coin_before = token_account_amount(&accounts.fund_coin_vault)?;
+    let pc_before = token_account_amount(&accounts.fund_pc_vault)?;
+
+    msg!("Withdrawing {} LP tokens from Raydium...", lp_amount);
+    invoke_signed(&ix, &liquidity_account_infos(accounts), signer_seeds)?;
+
+    let coin_after = token_account_amount(&accounts.fund_coin_vault)?;
+    let pc_after = token_account_amount(&accounts.fund_pc_vault)?;
+    verify_amount_received(coin_before, coin_after, min_coin_out)?;
+    verify_amount_received(pc_before, pc_after, min_pc_out)?;
+
+    msg!("Liquidity removal completed successfully.");
+    Ok(())
+}
+
+pub fn deposit_liquidity(
+    ctx: Context<ManageRaydiumLiquidity>,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    min_lp_out: u64,
+) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    check_lp_vault_owner(&ctx.accounts.pool)?;
+    increase_liquidity_on_raydium(&ctx.accounts.pool, max_coin_amount, max_pc_amount, min_lp_out, &[&seeds[..]])
+}
+
+pub fn withdraw_liquidity(
+    ctx: Context<ManageRaydiumLiquidity>,
+    lp_amount: u64,
+    min_coin_out: u64,
+    min_pc_out: u64,
+) -> ProgramResult {
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+
+    check_lp_vault_owner(&ctx.accounts.pool)?;
+    decrease_liquidity_on_raydium(&ctx.accounts.pool, lp_amount, min_coin_out, min_pc_out, &[&seeds[..]])
+}
+
+fn check_lp_vault_owner(accounts: &RaydiumLiquidity) -> ProgramResult {
+    let data = accounts.fund_lp_vault.try_borrow_data()?;
+    let lp_vault = TokenAccount::try_deserialize(&mut &data[..])?;
+
+    if lp_vault.owner != *accounts.fund_authority.key || lp_vault.mint != *accounts.lp_mint.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Checks the pool accounts against the allocation's pool config and the AMM's own state, and the
+/// fund's coin/pc vaults against the pool's mints, so rebalancing only moves the fund's own tokens
+fn check_configured_pool(accounts: &RaydiumLiquidity, pool_config: &LiquidityPoolAllocation) -> ProgramResult {
+    if *accounts.amm.owner != raydium_amm_v4::ID {
+        return Err(ProgramError::IllegalOwner);
+    }
+    if *accounts.amm.key != pool_config.amm
+        || *accounts.lp_mint.key != pool_config.lp_mint
+        || *accounts.fund_lp_vault.key != pool_config.lp_vault
+        || *accounts.pool_coin_vault.key != pool_config.pool_coin_vault
+        || *accounts.pool_pc_vault.key != pool_config.pool_pc_vault
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    {
+        let data = accounts.amm.try_borrow_data()?;
+        if data.len() < AMM_STATE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let recorded = |offset: usize| Pubkey::new(&data[offset..offset + 32]);
+
+        if *accounts.pool_coin_vault.key != recorded(AMM_COIN_VAULT_OFFSET)
+            || *accounts.pool_pc_vault.key != recorded(AMM_PC_VAULT_OFFSET)
+            || *accounts.amm_open_orders.key != recorded(AMM_OPEN_ORDERS_OFFSET)
+            || *accounts.serum_market.key != recorded(AMM_MARKET_OFFSET)
+            || *accounts.serum_program.key != recorded(AMM_SERUM_PROGRAM_OFFSET)
+            || *accounts.amm_target_orders.key != recorded(AMM_TARGET_ORDERS_OFFSET)
+        {
+            msg!("Accounts don't match Raydium pool {}", accounts.amm.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+
+    let token_account = |account: &AccountInfo| -> Result<TokenAccount, ProgramError> {
+        if *account.owner != token::ID {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let data = account.try_borrow_data()?;
+        TokenAccount::try_deserialize(&mut &data[..])
+    };
+    let pool_coin_vault = token_account(&accounts.pool_coin_vault)?;
+    let pool_pc_vault = token_account(&accounts.pool_pc_vault)?;
+    let fund_coin_vault = token_account(&accounts.fund_coin_vault)?;
+    let fund_pc_vault = token_account(&accounts.fund_pc_vault)?;
+
+    if fund_coin_vault.owner != *accounts.fund_authority.key
+        || fund_pc_vault.owner != *accounts.fund_authority.key
+        || fund_coin_vault.mint != pool_coin_vault.mint
+        || fund_pc_vault.mint != pool_pc_vault.mint
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    check_lp_vault_owner(accounts)
+}
+
+#[derive(Accounts)]
+pub struct EnsureLiquidityRepresentation<'info> {
+    #[account(has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+    #[account(has_one = fund)]
+    pub allocation_pda: Account<'info, Allocation>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+        constraint = fund_authority.key() == pool.fund_authority.key(),
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub pool: RaydiumLiquidity<'info>,
+    pub token_accounts: Vec<AccountInfo<'info>>, // Holding vaults, LP vaults, pool vaults and oracles referenced by the allocation
+}
+
+/// Value of the fund's LP position in a pool, and the value of a single LP token.
+/// The position covers both the LP vault and any LP tokens staked in a farm.
+pub fn get_lp_position_value(fund: &Fund, accounts: &[AccountInfo], pool: &LiquidityPoolAllocation) -> Result<(u64, u64), ProgramError> {
+    // Only the vaults and oracles configured for the pool are read, whatever else is passed
+    let configured_amount = |key: &Pubkey| -> Result<u64, ProgramError> {
+        let vault = find_account_by_key(accounts, key)?;
+        if *vault.owner != token::ID {
+            return Err(ProgramError::IllegalOwner);
+        }
+        token_account_amount(vault)
+    };
+    let lp_amount = configured_amount(&pool.lp_vault)?
+        .checked_add(fund.staked_lp_amount(&pool.lp_mint))
+        .ok_or(ProgramError::InvalidArgument)?;
+    let coin_reserve = configured_amount(&pool.pool_coin_vault)?;
+    let pc_reserve = configured_amount(&pool.pool_pc_vault)?;
+    let coin_price = get_current_market_price_from_raydium(fund, find_account_by_key(accounts, &pool.coin_price_oracle)?)?;
+    let pc_price = get_current_market_price_from_raydium(fund, find_account_by_key(accounts, &pool.pc_price_oracle)?)?;
+
+    let lp_mint = find_account_by_key(accounts, &pool.lp_mint)?;
+    if *lp_mint.owner != token::ID {
+        return Err(ProgramError::IllegalOwner);
+    }
+    let lp_supply = {
+        let data = lp_mint.try_borrow_data()?;
+        Mint::try_deserialize(&mut &data[..])?.supply
+    };
+    if lp_supply == 0 {
+        return Ok((0, 0));
+    }
+
+    let pool_value = coin_reserve as u128 * coin_price as u128 + pc_reserve as u128 * pc_price as u128;
+    let position_value = pool_value * lp_amount as u128 / lp_supply as u128;
+    let lp_price = pool_value / lp_supply as u128;
+
+    Ok((
+        u64::try_from(position_value).map_err(|_| ProgramError::InvalidArgument)?,
+        u64::try_from(lp_price).map_err(|_| ProgramError::InvalidArgument)?,
+    ))
+}
+
+/// Oracle value of every LP position configured in the allocation
+pub fn get_liquidity_value(fund: &Fund, allocation: &Allocation, accounts: &[AccountInfo]) -> Result<u64, ProgramError> {
+    let mut total_liquidity_value: u64 = 0;
+
+    for pool in allocation.liquidity_pools.iter() {
+        let (value, _) = get_lp_position_value(fund, accounts, pool)?;
+        total_liquidity_value = total_liquidity_value
+            .checked_add(value)
+            .ok_or(ProgramError::InvalidArgument)?;
+    }
+
+    Ok(total_liquidity_value)
+}
+
+/// `percentage` percent of `value`
+fn percentage_of(value: u64, percentage: u8) -> Result<u64, ProgramError> {
+    u64::try_from(value as u128 * percentage as u128 / 100).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Keeps the LP share of NAV within `liquidity_band_percentage` of `liquidity_target_percentage`.
+/// When total LP is below the band, the pool at `pool_index` is topped up toward its share of the
+/// target from the fund's coin/pc vaults; when above, it is drawn down toward its share. Each move
+/// is capped by the total deviation, so a pool is never moved against the fund-wide direction.
+pub fn ensure_liquidity_representation(ctx: Context<EnsureLiquidityRepresentation>, pool_index: u8) -> ProgramResult {
+    let allocation = &ctx.accounts.allocation_pda;
+    let accounts = &ctx.accounts.token_accounts;
+    let pool_config = allocation.liquidity_pools
+        .get(pool_index as usize)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    check_configured_pool(&ctx.accounts.pool, pool_config)?;
+
+    // Total NAV is the holdings plus every configured LP position
+    let total_liquidity_value = get_liquidity_value(&ctx.accounts.fund, allocation, accounts)?;
+    let total_fund_value = get_holdings_value(&ctx.accounts.fund, allocation, accounts)?
+        .checked_add(total_liquidity_value)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    let required_liquidity_value = percentage_of(total_fund_value, allocation.liquidity_target_percentage)?;
+    let band = percentage_of(total_fund_value, allocation.liquidity_band_percentage)?;
+    let below_band = (total_liquidity_value as u128 + band as u128) < required_liquidity_value as u128;
+    let above_band = total_liquidity_value as u128 > required_liquidity_value as u128 + band as u128;
+
+    if !below_band && !above_band {
+        msg!("LP value {} within band of target {}", total_liquidity_value, required_liquidity_value);
+        return Ok(());
+    }
+
+    let pool_target_value = percentage_of(required_liquidity_value, pool_config.percentage)?;
+    let (pool_value, lp_price) = get_lp_position_value(&ctx.accounts.fund, accounts, pool_config)?;
+    if lp_price == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let fund = &ctx.accounts.fund;
+    let fund_key = fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[fund.authority_bump]];
+    let coin_price = get_current_market_price_from_raydium(fund, find_account_by_key(accounts, &pool_config.coin_price_oracle)?)?;
+    let pc_price = get_current_market_price_from_raydium(fund, find_account_by_key(accounts, &pool_config.pc_price_oracle)?)?;
+    if coin_price == 0 || pc_price == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if below_band && pool_value < pool_target_value {
+        // Fund half of the shortfall from each side of the pool
+        let difference = (pool_target_value - pool_value).min(required_liquidity_value - total_liquidity_value);
+        let max_coin_amount = difference / 2 / coin_price;
+        let max_pc_amount = difference / 2 / pc_price;
+        let min_lp_out = apply_slippage(fund, difference / lp_price)?;
+
+        increase_liquidity_on_raydium(&ctx.accounts.pool, max_coin_amount, max_pc_amount, min_lp_out, &[&seeds[..]])?;
+    } else if above_band && pool_value > pool_target_value {
+        // LP staked in a farm can't be withdrawn here, so only the LP vault is drawn down
+        let difference = (pool_value - pool_target_value).min(total_liquidity_value - required_liquidity_value);
+        let lp_amount = (difference / lp_price).min(token_account_amount(&ctx.accounts.pool.fund_lp_vault)?);
+        if lp_amount == 0 {
+            msg!("No unstaked LP tokens to withdraw from pool {}", pool_config.amm);
+            return Ok(());
+        }
+        let difference = lp_amount
+            .checked_mul(lp_price)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let min_coin_out = apply_slippage(fund, difference / 2 / coin_price)?;
+        let min_pc_out = apply_slippage(fund, difference / 2 / pc_price)?;
+
+        decrease_liquidity_on_raydium(&ctx.accounts.pool, lp_amount, min_coin_out, min_pc_out, &[&seeds[..]])?;
+    } else {
+        msg!("Pool value {} already on the right side of its target {}", pool_value, pool_target_value);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
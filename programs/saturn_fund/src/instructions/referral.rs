@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, TokenAccount, Transfer};
+use crate::error::SaturnFundError;
+use crate::instructions::manage_holdings::find_account_by_key;
+use crate::state::fund::{Fund, FUND_AUTHORITY_SEED};
+use crate::state::referral::{ReferralAccount, REFERRAL_SEED};
+use crate::BPS_DENOMINATOR;
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct RegisterReferrer<'info> {
+    pub fund: Account<'info, Fund>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    #[account(
+        init,
+        seeds = [REFERRAL_SEED, fund.key().as_ref(), referrer.key().as_ref()],
+        bump = bump,
+        payer = referrer,
+        space = 8 + ReferralAccount::LEN,
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn register_referrer(ctx: Context<RegisterReferrer>, bump: u8) -> ProgramResult {
+    let referral = &mut ctx.accounts.referral_account;
+    referral.fund = ctx.accounts.fund.key();
+    referral.referrer = ctx.accounts.referrer.key();
+    referral.bump = bump;
+
+    msg!("Referrer {} registered", referral.referrer);
+    Ok(())
+}
+
+/// Credits `referral_fee_bps` of a mint fee to the referrer's account, passed among `accounts`.
+/// The credit stays in the fee vault, reserved from distribution until the referrer claims it.
+pub fn credit_referrer<'info>(
+    program_id: &Pubkey,
+    fund: &mut Account<'info, Fund>,
+    accounts: &[AccountInfo<'info>],
+    user: &Pubkey,
+    referrer: &Pubkey,
+    mint_fee: u64,
+    deposit: u64,
+) -> ProgramResult {
+    if referrer == user {
+        return Err(SaturnFundError::SelfReferral.into());
+    }
+
+    let fund_key = fund.key();
+    let referral_key = Pubkey::find_program_address(
+        &[REFERRAL_SEED, fund_key.as_ref(), referrer.as_ref()],
+        program_id,
+    ).0;
+    let referral_info = find_account_by_key(accounts, &referral_key)?;
+    let mut referral = Account::<ReferralAccount>::try_from(referral_info)?;
+    if referral.fund != fund_key || referral.referrer != *referrer {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let credit = (mint_fee as u128 * fund.referral_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    referral.unclaimed_fees = referral.unclaimed_fees
+        .checked_add(credit)
+        .ok_or(ProgramError::InvalidArgument)?;
+    referral.lifetime_fees = referral.lifetime_fees
+        .checked_add(credit)
+        .ok_or(ProgramError::InvalidArgument)?;
+    referral.referred_mints = referral.referred_mints.saturating_add(1);
+    referral.referred_volume = referral.referred_volume.saturating_add(deposit);
+    referral.exit(program_id)?;
+
+    fund.referral_fees_owed = fund.referral_fees_owed
+        .checked_add(credit)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    msg!("Credited {} of the mint fee to referrer {}", credit, referrer);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    #[account(mut)]
+    pub fund: Account<'info, Fund>,
+    pub referrer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [REFERRAL_SEED, fund.key().as_ref(), referrer.key().as_ref()],
+        bump = referral_account.bump,
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    #[account(mut, constraint = fee_vault.key() == fund.fee_vault)]
+    pub fee_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [FUND_AUTHORITY_SEED, fund.key().as_ref()],
+        bump = fund.authority_bump,
+    )]
+    pub fund_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, token::Token>,
+}
+
+pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> ProgramResult {
+    let amount = ctx.accounts.referral_account.unclaimed_fees;
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let fund_key = ctx.accounts.fund.key();
+    let seeds = &[FUND_AUTHORITY_SEED, fund_key.as_ref(), &[ctx.accounts.fund.authority_bump]];
+    let cpi_accounts = Transfer {
+        from: ctx.accounts.fee_vault.to_account_info(),
+        to: ctx.accounts.destination.to_account_info(),
+        authority: ctx.accounts.fund_authority.clone(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    token::transfer(CpiContext::new_with_signer(cpi_program, cpi_accounts, &[&seeds[..]]), amount)?;
+
+    ctx.accounts.referral_account.unclaimed_fees = 0;
+    let fund = &mut ctx.accounts.fund;
+    fund.referral_fees_owed = fund.referral_fees_owed.saturating_sub(amount);
+
+    msg!("Referrer {} claimed {}", ctx.accounts.referrer.key(), amount);
+    Ok(())
+}
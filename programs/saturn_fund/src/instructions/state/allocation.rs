@@ -1,16 +1,63 @@
 use anchor_lang::prelude::*;
 
+/// Seed prefix of a fund's allocation PDA, combined with the fund address
+pub const ALLOCATION_SEED: &[u8] = b"allocation";
+pub const MAX_HOLDINGS: usize = 16;
+pub const MAX_LIQUIDITY_POOLS: usize = 4;
+
 #[account]
 pub struct Allocation {
+    pub fund: Pubkey, // Fund this allocation belongs to
+    pub bump: u8,
     pub holding_tokens: Vec<TokenAllocation>,
     pub target_amount_percentage: u8,
     pub baseline_amount_percentage: u8,
     pub liquidity_pool_reward_percentage: u8,
     pub liquidity_pool_reward_destination: Pubkey,
+    pub liquidity_target_percentage: u8, // Share of NAV to hold as LP positions
+    pub liquidity_band_percentage: u8,   // Allowed drift either side of the LP target
+    pub liquidity_pools: Vec<LiquidityPoolAllocation>,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct TokenAllocation {
     pub token_mint: Pubkey,
     pub percentage: u8,
+    pub vault: Pubkey,        // Fund-owned token account holding this token
+    pub price_oracle: Pubkey, // Oracle account quoting this token's price
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LiquidityPoolAllocation {
+    pub amm: Pubkey,
+    pub lp_mint: Pubkey,
+    pub lp_vault: Pubkey,          // Fund-owned account holding this pool's LP tokens
+    pub pool_coin_vault: Pubkey,
+    pub pool_pc_vault: Pubkey,
+    pub coin_price_oracle: Pubkey,
+    pub pc_price_oracle: Pubkey,
+    pub percentage: u8,            // Share of the LP target allocated to this pool
+    pub farm: Pubkey,              // Raydium farm the LP tokens are staked in, default if none
+}
+
+impl Allocation {
+    /// Oracle configured for a holding's mint
+    pub fn price_oracle(&self, mint: &Pubkey) -> Option<Pubkey> {
+        self.holding_tokens.iter()
+            .find(|holding| holding.token_mint == *mint)
+            .map(|holding| holding.price_oracle)
+    }
+
+    pub const LEN: usize = 32 + 1
+        + 4 + MAX_HOLDINGS * TokenAllocation::LEN
+        + 1 * 3 + 32 + 1 * 2
+        + 4 + MAX_LIQUIDITY_POOLS * LiquidityPoolAllocation::LEN;
+}
+
+impl TokenAllocation {
+    pub const LEN: usize = 32 * 3 + 1;
+}
+
+impl LiquidityPoolAllocation {
+    pub const LEN: usize = 32 * 8 + 1;
 }
@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+/// Seed prefix of a user's allowlist entry, combined with the fund and user addresses
+pub const ALLOWLIST_SEED: &[u8] = b"allowlist";
+
+/// Entry allowing a user to mint and redeem while the fund runs in restricted mode
+#[account]
+pub struct Allowlist {
+    pub fund: Pubkey,
+    pub user: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+impl Allowlist {
+    pub const LEN: usize = 32 * 2 + 8 + 1;
+}
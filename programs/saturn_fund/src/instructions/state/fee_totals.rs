@@ -0,0 +1,18 @@
+use anchor_lang::prelude::*;
+
+/// Seed prefix of a fee destination's totals PDA, combined with the fund and destination addresses
+pub const FEE_TOTALS_SEED: &[u8] = b"fee_totals";
+
+/// Lifetime fees paid to one destination, kept apart from the fee split so the total
+/// survives the destination being dropped from and re-added to the split
+#[account]
+pub struct FeeTotals {
+    pub fund: Pubkey,
+    pub destination: Pubkey, // Token account of the fee mint
+    pub lifetime_fees: u64,  // Total paid to this destination
+    pub bump: u8,
+}
+
+impl FeeTotals {
+    pub const LEN: usize = 32 * 2 + 8 + 1;
+}
@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+use crate::instructions::collect_liquidity_pool_rewards::MAX_REWARD_RECIPIENTS;
+use crate::instructions::fee_distribution::MAX_FEE_RECIPIENTS;
+use crate::instructions::fee_handling::MAX_FEE_TIERS;
+use crate::state::allocation::MAX_LIQUIDITY_POOLS;
+
+/// Seed prefix of the PDA that owns the fund's vaults, combined with the fund address
+pub const FUND_AUTHORITY_SEED: &[u8] = b"fund_authority";
+
+#[account]
+pub struct Fund {
+    pub owner: Pubkey,
+    pub authority_bump: u8,            // Bump of the fund authority PDA
+    pub keeper: Pubkey,                // Bot allowed to run trading cranks alongside the owner
+    pub reward_recipients: Vec<RewardRecipient>, // Shares of harvested rewards paid out; the rest is reinvested
+    pub compound_rewards: bool,        // Swap the reinvested share into under-weight holdings on collection
+    pub profit_vesting_period: i64,    // Seconds over which compounded rewards unlock into NAV
+    pub locked_profit: u64,            // Value still locked as of the last harvest
+    pub last_harvest: i64,             // Unix timestamp of the last harvest that locked profit
+    pub share_mint: Pubkey,            // $STRN mint issued by this fund
+    pub fee_recipient: Pubkey,         // $STRN token account receiving minted fees
+    pub management_fee_bps: u64,       // Annual management fee
+    pub last_fee_accrual: i64,         // Unix timestamp management fees were last accrued up to
+    pub performance_fee_bps: u64,      // Share of NAV-per-share gains above the high-water mark, zero if disabled
+    pub crystallization_interval: i64, // Minimum seconds between performance fee crystallizations
+    pub high_water_mark: u64,          // Highest NAV per share fees were charged at, scaled by SHARE_PRICE_SCALE
+    pub last_crystallization: i64,     // Unix timestamp of the last performance fee crystallization
+    pub mint_fee_bps: u64,             // Charged on deposits before shares are issued
+    pub redeem_fee_bps: u64,           // Charged on redemption payouts
+    pub transfer_fee_bps: u64,         // Token-2022 fee on secondary $STRN transfers
+    pub mint_fee_tiers: Vec<MintFeeTier>,           // Lower mint fees for larger deposits, by ascending size
+    pub redeem_fee_schedule: Vec<RedeemFeeTier>,    // Exit fees decaying with holding period, by ascending period
+    pub fee_vault: Pubkey,             // Program-owned vault collecting mint and redeem fees
+    pub fee_recipients: Vec<FeeRecipient>, // Split of the fee vault
+    pub referral_fee_bps: u64,         // Share of the mint fee credited to the referrer
+    pub referral_fees_owed: u64,       // Referral credits held in the fee vault and not yet claimed
+    pub restricted: bool,              // Mint and redeem require an allowlist entry
+    pub compliance_authority: Pubkey,  // Manages the allowlist
+    pub max_slippage_bps: u64,         // Allowed deviation from the oracle price per trade
+    pub max_trade_notional: u64,       // Cap on the quote value of a single trade
+    pub max_epoch_notional: u64,       // Cap on the quote value traded within one epoch
+    pub epoch_duration: i64,           // Length of a trading epoch in seconds
+    pub epoch_start: i64,              // Unix timestamp the current epoch began
+    pub epoch_notional_traded: u64,    // Quote value traded so far in the current epoch
+    pub oracle_program: Pubkey,        // Program owning every price oracle the fund reads
+    pub max_oracle_age: i64,           // Oldest oracle price, in seconds, trades and valuations accept
+    pub staked_lp: Vec<StakedLiquidity>, // LP tokens held by Raydium farms on the fund's behalf
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MintFeeTier {
+    pub min_amount: u64, // Deposits of at least this size pay `fee_bps`
+    pub fee_bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RedeemFeeTier {
+    pub min_holding_seconds: i64, // Shares held at least this long pay `fee_bps`
+    pub fee_bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum FeeRecipientKind {
+    Treasury,
+    StakerPool,
+    Referrer,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FeeRecipient {
+    pub kind: FeeRecipientKind,
+    pub destination: Pubkey, // Token account of the fee mint
+    pub bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RewardRecipient {
+    pub destination: Pubkey, // Token account of the reward mint
+    pub bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StakedLiquidity {
+    pub lp_mint: Pubkey,
+    pub farm: Pubkey,
+    pub amount: u64,
+}
+
+impl MintFeeTier {
+    pub const LEN: usize = 8 * 2;
+}
+
+impl RedeemFeeTier {
+    pub const LEN: usize = 8 * 2;
+}
+
+impl FeeRecipient {
+    pub const LEN: usize = 1 + 32 + 8;
+}
+
+impl RewardRecipient {
+    pub const LEN: usize = 32 + 8;
+}
+
+impl StakedLiquidity {
+    pub const LEN: usize = 32 * 2 + 8;
+}
+
+impl Fund {
+    // Every list is sized to its cap, so updates never outgrow the account
+    pub const LEN: usize = 32 + 1 + 32
+        + 4 + MAX_REWARD_RECIPIENTS * RewardRecipient::LEN
+        + 1 + 8 * 3
+        + 32 * 2 + 8 * 2
+        + 8 * 4
+        + 8 * 3
+        + 4 + MAX_FEE_TIERS * MintFeeTier::LEN
+        + 4 + MAX_FEE_TIERS * RedeemFeeTier::LEN
+        + 32
+        + 4 + MAX_FEE_RECIPIENTS * FeeRecipient::LEN
+        + 8 * 2
+        + 1 + 32
+        + 8 * 6
+        + 32 + 8
+        + 4 + MAX_LIQUIDITY_POOLS * StakedLiquidity::LEN;
+
+    /// Part of the last locked profit that has not yet vested, unlocking linearly over the vesting period
+    pub fn current_locked_profit(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_harvest);
+        if self.profit_vesting_period <= 0 || elapsed >= self.profit_vesting_period {
+            return 0;
+        }
+
+        let remaining = (self.profit_vesting_period - elapsed.max(0)) as u128;
+        (self.locked_profit as u128 * remaining / self.profit_vesting_period as u128) as u64
+    }
+
+    /// Locks newly harvested value on top of what is still vesting, restarting the vesting period
+    pub fn lock_profit(&mut self, value: u64, now: i64) {
+        self.locked_profit = self.current_locked_profit(now).saturating_add(value);
+        self.last_harvest = now;
+    }
+
+    /// LP tokens of `lp_mint` currently staked in farms
+    pub fn staked_lp_amount(&self, lp_mint: &Pubkey) -> u64 {
+        self.staked_lp.iter()
+            .filter(|staked| staked.lp_mint == *lp_mint)
+            .map(|staked| staked.amount)
+            .sum()
+    }
+}
@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+/// Seed prefix of a holding's market record, combined with the fund and base mint
+pub const HOLDING_MARKET_SEED: &[u8] = b"holding_market";
+/// Seed prefix of the fund's open orders account, combined with the fund and market
+pub const OPEN_ORDERS_SEED: &[u8] = b"open_orders";
+
+/// OpenBook market a holding is traded on, with the fund's open orders account for it
+#[account]
+pub struct HoldingMarket {
+    pub fund: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub dex_program: Pubkey,
+    pub market: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub request_queue: Pubkey,
+    pub event_queue: Pubkey,
+    pub coin_vault: Pubkey,        // Market's base vault
+    pub pc_vault: Pubkey,          // Market's quote vault
+    pub vault_signer: Pubkey,
+    pub open_orders: Pubkey,       // PDA-owned open orders account of the fund
+    pub fund_base_vault: Pubkey,   // Fund vault receiving/paying the base token
+    pub fund_quote_vault: Pubkey,  // Fund vault receiving/paying the quote token
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+    pub bump: u8,
+    pub open_orders_bump: u8,
+}
+
+impl HoldingMarket {
+    pub const LEN: usize = 32 * 15 + 8 * 2 + 1 * 2;
+}
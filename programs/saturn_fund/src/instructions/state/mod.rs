@@ -0,0 +1,8 @@
+pub mod accounts;
+pub mod allocation;
+pub mod fund;
+pub mod market;
+pub mod position;
+pub mod referral;
+pub mod allowlist;
+pub mod fee_totals;
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+/// Seed prefix of a holder's position PDA, combined with the fund and holder addresses
+pub const USER_POSITION_SEED: &[u8] = b"user_position";
+
+#[account]
+pub struct UserPosition {
+    pub fund: Pubkey,
+    pub owner: Pubkey,
+    pub shares: u64,             // $STRN minted to this holder and not yet redeemed
+    pub deposit_timestamp: i64,  // Share-weighted average time of the holder's deposits
+    pub bump: u8,
+}
+
+impl UserPosition {
+    pub const LEN: usize = 32 * 2 + 8 * 2 + 1;
+
+    /// Adds newly minted shares, moving the deposit time toward `now` in proportion to their weight
+    pub fn record_deposit(&mut self, shares: u64, now: i64) -> ProgramResult {
+        let total_shares = self.shares
+            .checked_add(shares)
+            .ok_or(ProgramError::InvalidArgument)?;
+        if total_shares == 0 {
+            return Ok(());
+        }
+
+        let weighted = self.shares as i128 * self.deposit_timestamp as i128 + shares as i128 * now as i128;
+        self.deposit_timestamp = (weighted / total_shares as i128) as i64;
+        self.shares = total_shares;
+        Ok(())
+    }
+
+    /// Removes redeemed shares. Shares received by transfer were never deposited here, so the count floors at zero.
+    pub fn record_redemption(&mut self, shares: u64) {
+        self.shares = self.shares.saturating_sub(shares);
+    }
+
+    pub fn holding_period(&self, now: i64) -> i64 {
+        now.saturating_sub(self.deposit_timestamp).max(0)
+    }
+}
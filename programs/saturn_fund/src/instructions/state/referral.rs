@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+/// Seed prefix of a referrer's account PDA, combined with the fund and referrer addresses
+pub const REFERRAL_SEED: &[u8] = b"referral";
+
+#[account]
+pub struct ReferralAccount {
+    pub fund: Pubkey,
+    pub referrer: Pubkey,
+    pub unclaimed_fees: u64,   // Credited mint fees held in the fee vault until claimed
+    pub lifetime_fees: u64,    // Total mint fees ever credited
+    pub referred_mints: u64,   // Number of mints made with this referrer
+    pub referred_volume: u64,  // Total deposits made with this referrer
+    pub bump: u8,
+}
+
+impl ReferralAccount {
+    pub const LEN: usize = 32 * 2 + 8 * 4 + 1;
+}
@@ -0,0 +1,584 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token::TokenAccount;
+use serum_dex::matching::Side;
+use serum_dex::state::Market as DexMarket;
+use spl_token_swap::state::SwapVersion;
+use crate::error::SaturnFundError;
+use crate::instructions::openbook::{
+    holding_market_address, ioc_order, is_dex_program, place_order, settle_funds, OpenBookMarket, OPENBOOK_TAKER_FEE_BPS,
+};
+use crate::instructions::raydium_integration::{
+    raydium_amm_v4, swap_via_raydium, token_account_amount, SwapViaRaydium, AMM_COIN_VAULT_OFFSET, AMM_MARKET_OFFSET,
+    AMM_OPEN_ORDERS_OFFSET, AMM_PC_VAULT_OFFSET, AMM_SERUM_PROGRAM_OFFSET, AMM_STATE_LEN, AMM_TARGET_ORDERS_OFFSET,
+};
+use crate::instructions::trade_limits::verify_amount_received;
+use crate::state::market::{HoldingMarket, OPEN_ORDERS_SEED};
+use crate::BPS_DENOMINATOR;
+
+/// Raydium AMM v4 trade fee
+const RAYDIUM_FEE_BPS: u64 = 25;
+
+/// Seed of the Raydium AMM v4 authority PDA
+const RAYDIUM_AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Accounts of one swap leg on the fund's side, independent of the venue used
+pub struct SwapLeg<'info> {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub source: AccountInfo<'info>,      // Fund vault paying the input
+    pub destination: AccountInfo<'info>, // Fund vault receiving the output
+    pub authority: AccountInfo<'info>,   // Fund PDA owning the source vault
+    pub token_program: AccountInfo<'info>,
+}
+
+/// A place the fund can swap through. Quotes are read from on-chain venue state,
+/// and every execution fails with `SlippageExceeded` below `minimum_amount_out`.
+pub trait SwapVenue<'info> {
+    fn name(&self) -> &'static str;
+    fn supports(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> Result<bool, ProgramError>;
+    fn quote(&self, input_mint: &Pubkey, amount_in: u64) -> Result<u64, ProgramError>;
+    fn execute(
+        &self,
+        leg: &SwapLeg<'info>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult;
+}
+
+fn token_account_mint(account: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    let data = account.try_borrow_data()?;
+    Ok(TokenAccount::try_deserialize(&mut &data[..])?.mint)
+}
+
+/// Constant-product output for `amount_in` after a fee in basis points
+fn constant_product_out(amount_in: u64, reserve_in: u64, reserve_out: u64, fee_bps: u64) -> u64 {
+    let amount_in = amount_in as u128 * (BPS_DENOMINATOR - fee_bps) as u128 / BPS_DENOMINATOR as u128;
+    let denominator = reserve_in as u128 + amount_in;
+    if denominator == 0 {
+        return 0;
+    }
+    (reserve_out as u128 * amount_in / denominator) as u64
+}
+
+pub struct RaydiumAmmVenue<'info> {
+    pub amm_program: AccountInfo<'info>,
+    pub amm: AccountInfo<'info>,
+    pub amm_authority: AccountInfo<'info>,
+    pub amm_open_orders: AccountInfo<'info>,
+    pub amm_target_orders: AccountInfo<'info>,
+    pub pool_coin_vault: AccountInfo<'info>,
+    pub pool_pc_vault: AccountInfo<'info>,
+    pub serum_program: AccountInfo<'info>,
+    pub serum_market: AccountInfo<'info>,
+    pub serum_bids: AccountInfo<'info>,
+    pub serum_asks: AccountInfo<'info>,
+    pub serum_event_queue: AccountInfo<'info>,
+    pub serum_coin_vault: AccountInfo<'info>,
+    pub serum_pc_vault: AccountInfo<'info>,
+    pub serum_vault_signer: AccountInfo<'info>,
+}
+
+impl<'info> RaydiumAmmVenue<'info> {
+    pub const ACCOUNTS_LEN: usize = 15;
+
+    fn from_accounts(accounts: &[AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let venue = RaydiumAmmVenue {
+            amm_program: accounts[0].clone(),
+            amm: accounts[1].clone(),
+            amm_authority: accounts[2].clone(),
+            amm_open_orders: accounts[3].clone(),
+            amm_target_orders: accounts[4].clone(),
+            pool_coin_vault: accounts[5].clone(),
+            pool_pc_vault: accounts[6].clone(),
+            serum_program: accounts[7].clone(),
+            serum_market: accounts[8].clone(),
+            serum_bids: accounts[9].clone(),
+            serum_asks: accounts[10].clone(),
+            serum_event_queue: accounts[11].clone(),
+            serum_coin_vault: accounts[12].clone(),
+            serum_pc_vault: accounts[13].clone(),
+            serum_vault_signer: accounts[14].clone(),
+        };
+        venue.verify_pool()?;
+        Ok(venue)
+    }
+
+    /// Checks the vaults and market accounts against the ones the AMM's own state records,
+    /// so quotes can't be read from vaults of a different pool
+    fn verify_pool(&self) -> ProgramResult {
+        if *self.amm.owner != raydium_amm_v4::ID {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let (amm_authority, _) = Pubkey::find_program_address(&[RAYDIUM_AMM_AUTHORITY_SEED], &raydium_amm_v4::ID);
+
+        let data = self.amm.try_borrow_data()?;
+        if data.len() < AMM_STATE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let recorded = |offset: usize| Pubkey::new(&data[offset..offset + 32]);
+
+        if *self.amm_authority.key != amm_authority
+            || *self.pool_coin_vault.key != recorded(AMM_COIN_VAULT_OFFSET)
+            || *self.pool_pc_vault.key != recorded(AMM_PC_VAULT_OFFSET)
+            || *self.amm_open_orders.key != recorded(AMM_OPEN_ORDERS_OFFSET)
+            || *self.serum_market.key != recorded(AMM_MARKET_OFFSET)
+            || *self.serum_program.key != recorded(AMM_SERUM_PROGRAM_OFFSET)
+            || *self.amm_target_orders.key != recorded(AMM_TARGET_ORDERS_OFFSET)
+        {
+            msg!("Accounts don't match Raydium pool {}", self.amm.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+}
+
+impl<'info> SwapVenue<'info> for RaydiumAmmVenue<'info> {
+    fn name(&self) -> &'static str {
+        "Raydium AMM"
+    }
+
+    fn supports(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> Result<bool, ProgramError> {
+        let coin_mint = token_account_mint(&self.pool_coin_vault)?;
+        let pc_mint = token_account_mint(&self.pool_pc_vault)?;
+        Ok((*input_mint == coin_mint && *output_mint == pc_mint) || (*input_mint == pc_mint && *output_mint == coin_mint))
+    }
+
+    fn quote(&self, input_mint: &Pubkey, amount_in: u64) -> Result<u64, ProgramError> {
+        // Reserves held by the AMM's open orders are ignored, which keeps the quote conservative
+        let coin_reserve = token_account_amount(&self.pool_coin_vault)?;
+        let pc_reserve = token_account_amount(&self.pool_pc_vault)?;
+
+        if *input_mint == token_account_mint(&self.pool_coin_vault)? {
+            Ok(constant_product_out(amount_in, coin_reserve, pc_reserve, RAYDIUM_FEE_BPS))
+        } else {
+            Ok(constant_product_out(amount_in, pc_reserve, coin_reserve, RAYDIUM_FEE_BPS))
+        }
+    }
+
+    fn execute(
+        &self,
+        leg: &SwapLeg<'info>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let accounts = SwapViaRaydium {
+            amm_program: self.amm_program.clone(),
+            amm: self.amm.clone(),
+            amm_authority: self.amm_authority.clone(),
+            amm_open_orders: self.amm_open_orders.clone(),
+            amm_target_orders: self.amm_target_orders.clone(),
+            pool_coin_vault: self.pool_coin_vault.clone(),
+            pool_pc_vault: self.pool_pc_vault.clone(),
+            serum_program: self.serum_program.clone(),
+            serum_market: self.serum_market.clone(),
+            serum_bids: self.serum_bids.clone(),
+            serum_asks: self.serum_asks.clone(),
+            serum_event_queue: self.serum_event_queue.clone(),
+            serum_coin_vault: self.serum_coin_vault.clone(),
+            serum_pc_vault: self.serum_pc_vault.clone(),
+            serum_vault_signer: self.serum_vault_signer.clone(),
+            source_token_account: leg.source.clone(),
+            destination_token_account: leg.destination.clone(),
+            source_owner: leg.authority.clone(),
+            token_program: Program::try_from(&leg.token_program)?,
+        };
+
+        swap_via_raydium(&accounts, amount_in, minimum_amount_out, signer_seeds)
+    }
+}
+
+pub struct OpenBookVenue<'info> {
+    pub holding_market: HoldingMarket,
+    pub dex_program: AccountInfo<'info>,
+    pub market: AccountInfo<'info>,
+    pub open_orders: AccountInfo<'info>,
+    pub request_queue: AccountInfo<'info>,
+    pub event_queue: AccountInfo<'info>,
+    pub bids: AccountInfo<'info>,
+    pub asks: AccountInfo<'info>,
+    pub coin_vault: AccountInfo<'info>,
+    pub pc_vault: AccountInfo<'info>,
+    pub vault_signer: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+}
+
+impl<'info> OpenBookVenue<'info> {
+    /// Program, holding market record, the nine market accounts and the rent sysvar
+    pub const ACCOUNTS_LEN: usize = 12;
+
+    fn from_accounts(program_id: &Pubkey, fund: &Pubkey, accounts: &[AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let holding_market: Account<HoldingMarket> = Account::try_from(&accounts[1])?;
+        // Only markets registered for this fund, at their record and open orders PDAs, may be used
+        let open_orders = Pubkey::create_program_address(
+            &[OPEN_ORDERS_SEED, fund.as_ref(), holding_market.market.as_ref(), &[holding_market.open_orders_bump]],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+        if holding_market.fund != *fund
+            || *accounts[1].key != holding_market_address(fund, &holding_market.base_mint, program_id)
+            || holding_market.open_orders != open_orders
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let venue = OpenBookVenue {
+            holding_market: (*holding_market).clone(),
+            dex_program: accounts[0].clone(),
+            market: accounts[2].clone(),
+            open_orders: accounts[3].clone(),
+            request_queue: accounts[4].clone(),
+            event_queue: accounts[5].clone(),
+            bids: accounts[6].clone(),
+            asks: accounts[7].clone(),
+            coin_vault: accounts[8].clone(),
+            pc_vault: accounts[9].clone(),
+            vault_signer: accounts[10].clone(),
+            rent: accounts[11].clone(),
+        };
+
+        // Only the accounts recorded for the holding can be traded against
+        let record = &venue.holding_market;
+        if *venue.dex_program.key != record.dex_program
+            || *venue.market.key != record.market
+            || *venue.open_orders.key != record.open_orders
+            || *venue.request_queue.key != record.request_queue
+            || *venue.event_queue.key != record.event_queue
+            || *venue.bids.key != record.bids
+            || *venue.asks.key != record.asks
+            || *venue.coin_vault.key != record.coin_vault
+            || *venue.pc_vault.key != record.pc_vault
+            || *venue.vault_signer.key != record.vault_signer
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(venue)
+    }
+
+    /// Best (price in lots, quantity in lots) on the side a taker of `side` would hit
+    fn top_of_book(&self, side: Side) -> Result<Option<(u64, u64)>, ProgramError> {
+        let market = DexMarket::load(&self.market, self.dex_program.key, false)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        let level = match side {
+            Side::Bid => {
+                let asks = market.load_asks_mut(&self.asks).map_err(|_| ProgramError::InvalidAccountData)?;
+                asks.find_min().and_then(|handle| asks.get(handle)).and_then(|node| node.as_leaf())
+                    .map(|leaf| (leaf.price().get(), leaf.quantity()))
+            }
+            Side::Ask => {
+                let bids = market.load_bids_mut(&self.bids).map_err(|_| ProgramError::InvalidAccountData)?;
+                bids.find_max().and_then(|handle| bids.get(handle)).and_then(|node| node.as_leaf())
+                    .map(|leaf| (leaf.price().get(), leaf.quantity()))
+            }
+        };
+
+        Ok(level)
+    }
+
+    fn side_for(&self, input_mint: &Pubkey) -> Side {
+        if *input_mint == self.holding_market.quote_mint {
+            Side::Bid
+        } else {
+            Side::Ask
+        }
+    }
+}
+
+impl<'info> SwapVenue<'info> for OpenBookVenue<'info> {
+    fn name(&self) -> &'static str {
+        "OpenBook"
+    }
+
+    fn supports(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> Result<bool, ProgramError> {
+        let record = &self.holding_market;
+        Ok((*input_mint == record.base_mint && *output_mint == record.quote_mint)
+            || (*input_mint == record.quote_mint && *output_mint == record.base_mint))
+    }
+
+    fn quote(&self, input_mint: &Pubkey, amount_in: u64) -> Result<u64, ProgramError> {
+        // Only the best level is considered, so large orders are quoted conservatively
+        let side = self.side_for(input_mint);
+        let (price_lots, quantity_lots) = match self.top_of_book(side)? {
+            Some(level) => level,
+            None => return Ok(0),
+        };
+
+        let base_lot_size = self.holding_market.base_lot_size as u128;
+        let quote_lot_size = self.holding_market.quote_lot_size as u128;
+        let available_base = quantity_lots as u128 * base_lot_size;
+        let quote_per_base_lot = price_lots as u128 * quote_lot_size;
+
+        let out = match side {
+            Side::Bid => {
+                let base_lots = amount_in as u128 / quote_per_base_lot.max(1);
+                let base_out = base_lots * base_lot_size;
+                base_out.min(available_base)
+            }
+            Side::Ask => {
+                // Only whole lots are sold
+                let base_in = (amount_in as u128 / base_lot_size * base_lot_size).min(available_base);
+                base_in * quote_per_base_lot / base_lot_size
+            }
+        };
+        let out = out * (BPS_DENOMINATOR - OPENBOOK_TAKER_FEE_BPS) as u128 / BPS_DENOMINATOR as u128;
+
+        u64::try_from(out).map_err(|_| ProgramError::InvalidArgument)
+    }
+
+    fn execute(
+        &self,
+        leg: &SwapLeg<'info>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let side = self.side_for(&leg.input_mint);
+        let record = &self.holding_market;
+        let (fund_base_vault, fund_quote_vault) = match side {
+            Side::Bid => (&leg.destination, &leg.source),
+            Side::Ask => (&leg.source, &leg.destination),
+        };
+        if *fund_base_vault.key != record.fund_base_vault || *fund_quote_vault.key != record.fund_quote_vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let market = OpenBookMarket {
+            dex_program: &self.dex_program,
+            market: &self.market,
+            open_orders: &self.open_orders,
+            request_queue: &self.request_queue,
+            event_queue: &self.event_queue,
+            bids: &self.bids,
+            asks: &self.asks,
+            coin_vault: &self.coin_vault,
+            pc_vault: &self.pc_vault,
+            vault_signer: &self.vault_signer,
+            fund_base_vault,
+            fund_quote_vault,
+            fund_authority: leg.authority.clone(),
+            token_program: leg.token_program.clone(),
+            rent: self.rent.clone(),
+        };
+
+        let order = ioc_order(record, side, amount_in, minimum_amount_out)?;
+
+        let balance_before = token_account_amount(&leg.destination)?;
+
+        place_order(&market, record, side, order.max_base_qty, order.max_quote_qty, order.limit_price_lots, signer_seeds)?;
+        settle_funds(&market, signer_seeds)?;
+
+        let balance_after = token_account_amount(&leg.destination)?;
+        verify_amount_received(balance_before, balance_after, minimum_amount_out)
+    }
+}
+
+pub struct TokenSwapVenue<'info> {
+    pub swap_program: AccountInfo<'info>,
+    pub swap: AccountInfo<'info>,
+    pub swap_authority: AccountInfo<'info>,
+    pub pool_token_a: AccountInfo<'info>,
+    pub pool_token_b: AccountInfo<'info>,
+    pub pool_mint: AccountInfo<'info>,
+    pub pool_fee_account: AccountInfo<'info>,
+}
+
+impl<'info> TokenSwapVenue<'info> {
+    pub const ACCOUNTS_LEN: usize = 7;
+
+    fn from_accounts(accounts: &[AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let venue = TokenSwapVenue {
+            swap_program: accounts[0].clone(),
+            swap: accounts[1].clone(),
+            swap_authority: accounts[2].clone(),
+            pool_token_a: accounts[3].clone(),
+            pool_token_b: accounts[4].clone(),
+            pool_mint: accounts[5].clone(),
+            pool_fee_account: accounts[6].clone(),
+        };
+        venue.verify_pool()?;
+        Ok(venue)
+    }
+
+    /// Checks the pool vaults, mint, fee account and authority against the swap's own state
+    fn verify_pool(&self) -> ProgramResult {
+        if *self.swap.owner != *self.swap_program.key {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let swap = SwapVersion::unpack(&self.swap.try_borrow_data()?)?;
+        let authority = Pubkey::create_program_address(&[self.swap.key.as_ref(), &[swap.nonce()]], self.swap_program.key)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        if *self.swap_authority.key != authority
+            || self.pool_token_a.key != swap.token_a_account()
+            || self.pool_token_b.key != swap.token_b_account()
+            || self.pool_mint.key != swap.pool_mint()
+            || self.pool_fee_account.key != swap.pool_fee_account()
+        {
+            msg!("Accounts don't match token-swap pool {}", self.swap.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Pool (source, destination) vaults for swapping out of `input_mint`
+    fn pool_vaults(&self, input_mint: &Pubkey) -> Result<(&AccountInfo<'info>, &AccountInfo<'info>), ProgramError> {
+        if *input_mint == token_account_mint(&self.pool_token_a)? {
+            Ok((&self.pool_token_a, &self.pool_token_b))
+        } else {
+            Ok((&self.pool_token_b, &self.pool_token_a))
+        }
+    }
+}
+
+impl<'info> SwapVenue<'info> for TokenSwapVenue<'info> {
+    fn name(&self) -> &'static str {
+        "SPL token-swap"
+    }
+
+    fn supports(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> Result<bool, ProgramError> {
+        let mint_a = token_account_mint(&self.pool_token_a)?;
+        let mint_b = token_account_mint(&self.pool_token_b)?;
+        Ok((*input_mint == mint_a && *output_mint == mint_b) || (*input_mint == mint_b && *output_mint == mint_a))
+    }
+
+    fn quote(&self, input_mint: &Pubkey, amount_in: u64) -> Result<u64, ProgramError> {
+        let swap = SwapVersion::unpack(&self.swap.try_borrow_data()?)?;
+        let fees = swap.fees();
+        let total_fee = fees.trading_fee(amount_in as u128)
+            .and_then(|fee| fees.owner_trading_fee(amount_in as u128).map(|owner_fee| fee + owner_fee))
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        let (pool_source, pool_destination) = self.pool_vaults(input_mint)?;
+        let reserve_in = token_account_amount(pool_source)? as u128;
+        let reserve_out = token_account_amount(pool_destination)? as u128;
+
+        // Assumes a constant-product curve, which is what the fund's pools use
+        let amount_in = (amount_in as u128).saturating_sub(total_fee);
+        let denominator = reserve_in + amount_in;
+        if denominator == 0 {
+            return Ok(0);
+        }
+        Ok((reserve_out * amount_in / denominator) as u64)
+    }
+
+    fn execute(
+        &self,
+        leg: &SwapLeg<'info>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let (pool_source, pool_destination) = self.pool_vaults(&leg.input_mint)?;
+
+        let ix = spl_token_swap::instruction::swap(
+            self.swap_program.key,
+            leg.token_program.key,
+            self.swap.key,
+            self.swap_authority.key,
+            leg.authority.key,
+            leg.source.key,
+            pool_source.key,
+            pool_destination.key,
+            leg.destination.key,
+            self.pool_mint.key,
+            self.pool_fee_account.key,
+            None,
+            spl_token_swap::instruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            },
+        )?;
+
+        let balance_before = token_account_amount(&leg.destination)?;
+
+        invoke_signed(
+            &ix,
+            &[
+                self.swap.clone(),
+                self.swap_authority.clone(),
+                leg.authority.clone(),
+                leg.source.clone(),
+                pool_source.clone(),
+                pool_destination.clone(),
+                leg.destination.clone(),
+                self.pool_mint.clone(),
+                self.pool_fee_account.clone(),
+                leg.token_program.clone(),
+                self.swap_program.clone(),
+            ],
+            signer_seeds,
+        )?;
+
+        let balance_after = token_account_amount(&leg.destination)?;
+        verify_amount_received(balance_before, balance_after, minimum_amount_out)
+    }
+}
+
+/// Collects every venue of `fund` whose accounts appear in `accounts`. Each venue is a contiguous
+/// group starting with its program account; accounts outside a group are skipped, so
+/// venue groups can be appended after the holding vaults and oracles.
+pub fn parse_venues<'info>(
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+) -> Result<Vec<Box<dyn SwapVenue<'info> + 'info>>, ProgramError> {
+    let mut venues: Vec<Box<dyn SwapVenue<'info> + 'info>> = Vec::new();
+    let mut index = 0;
+
+    while index < accounts.len() {
+        let key = accounts[index].key;
+        let remaining = &accounts[index..];
+
+        if *key == raydium_amm_v4::ID && remaining.len() >= RaydiumAmmVenue::ACCOUNTS_LEN {
+            venues.push(Box::new(RaydiumAmmVenue::from_accounts(remaining)?));
+            index += RaydiumAmmVenue::ACCOUNTS_LEN;
+        } else if *key == spl_token_swap::id() && remaining.len() >= TokenSwapVenue::ACCOUNTS_LEN {
+            venues.push(Box::new(TokenSwapVenue::from_accounts(remaining)?));
+            index += TokenSwapVenue::ACCOUNTS_LEN;
+        } else if is_dex_program(key) && remaining.len() >= OpenBookVenue::ACCOUNTS_LEN {
+            venues.push(Box::new(OpenBookVenue::from_accounts(program_id, fund, remaining)?));
+            index += OpenBookVenue::ACCOUNTS_LEN;
+        } else {
+            index += 1;
+        }
+    }
+
+    Ok(venues)
+}
+
+/// Quotes the leg on every supporting venue and executes it on the one with the best output
+pub fn route_swap<'info>(
+    venues: &[Box<dyn SwapVenue<'info> + 'info>],
+    leg: &SwapLeg<'info>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut best: Option<(&Box<dyn SwapVenue<'info> + 'info>, u64)> = None;
+
+    for venue in venues.iter() {
+        if !venue.supports(&leg.input_mint, &leg.output_mint)? {
+            continue;
+        }
+
+        let quoted = venue.quote(&leg.input_mint, amount_in)?;
+        msg!("{} quotes {} for {}", venue.name(), quoted, amount_in);
+
+        if best.map_or(true, |(_, best_quote)| quoted > best_quote) {
+            best = Some((venue, quoted));
+        }
+    }
+
+    let (venue, quoted) = best.ok_or::<ProgramError>(SaturnFundError::NoSwapVenue.into())?;
+    if quoted < minimum_amount_out {
+        msg!("Best quote {} is below the minimum {}", quoted, minimum_amount_out);
+        return Err(SaturnFundError::SlippageExceeded.into());
+    }
+
+    msg!("Routing swap through {}", venue.name());
+    venue.execute(leg, amount_in, minimum_amount_out, signer_seeds)
+}
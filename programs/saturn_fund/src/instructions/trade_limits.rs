@@ -0,0 +1,117 @@
+use anchor_lang::prelude::*;
+use crate::error::SaturnFundError;
+use crate::state::fund::Fund;
+use crate::BPS_DENOMINATOR;
+
+/// Limits a new fund starts with, until the owner configures its own
+pub const DEFAULT_MAX_SLIPPAGE_BPS: u64 = 100;
+pub const DEFAULT_MAX_TRADE_NOTIONAL: u64 = u64::MAX;
+pub const DEFAULT_MAX_EPOCH_NOTIONAL: u64 = u64::MAX;
+pub const DEFAULT_EPOCH_DURATION: i64 = 24 * 60 * 60;
+pub const DEFAULT_MAX_ORACLE_AGE: i64 = 60;
+
+#[derive(Accounts)]
+pub struct UpdateTradeLimits<'info> {
+    #[account(mut, has_one = owner)]
+    pub fund: Account<'info, Fund>,
+    pub owner: Signer<'info>,
+}
+
+pub fn update_trade_limits(
+    ctx: Context<UpdateTradeLimits>,
+    max_slippage_bps: u64,
+    max_trade_notional: u64,
+    max_epoch_notional: u64,
+    epoch_duration: i64,
+) -> ProgramResult {
+    if max_slippage_bps > BPS_DENOMINATOR || epoch_duration <= 0 || max_trade_notional > max_epoch_notional {
+        return Err(SaturnFundError::InvalidTradeLimits.into());
+    }
+
+    let fund = &mut ctx.accounts.fund;
+    fund.max_slippage_bps = max_slippage_bps;
+    fund.max_trade_notional = max_trade_notional;
+    fund.max_epoch_notional = max_epoch_notional;
+    fund.epoch_duration = epoch_duration;
+
+    msg!(
+        "Trade limits updated: slippage {} bps, per-trade {}, per-epoch {}",
+        max_slippage_bps,
+        max_trade_notional,
+        max_epoch_notional
+    );
+    Ok(())
+}
+
+/// Sets the program owning the fund's price oracles and how old a price may be
+pub fn set_oracle_config(ctx: Context<UpdateTradeLimits>, oracle_program: Pubkey, max_oracle_age: i64) -> ProgramResult {
+    if max_oracle_age <= 0 {
+        return Err(SaturnFundError::InvalidTradeLimits.into());
+    }
+
+    let fund = &mut ctx.accounts.fund;
+    fund.oracle_program = oracle_program;
+    fund.max_oracle_age = max_oracle_age;
+
+    msg!("Oracle program set to {}, max age {} seconds", oracle_program, max_oracle_age);
+    Ok(())
+}
+
+pub fn set_keeper(ctx: Context<UpdateTradeLimits>, keeper: Pubkey) -> ProgramResult {
+    ctx.accounts.fund.keeper = keeper;
+
+    msg!("Keeper set to {}", keeper);
+    Ok(())
+}
+
+/// Minimum acceptable output for swapping `amount_in` at the given oracle prices,
+/// reduced by the fund's slippage tolerance
+pub fn min_amount_out(fund: &Fund, amount_in: u64, price_in: u64, price_out: u64) -> Result<u64, ProgramError> {
+    if price_out == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let expected_out = amount_in as u128 * price_in as u128 / price_out as u128;
+    let expected_out = u64::try_from(expected_out).map_err(|_| ProgramError::InvalidArgument)?;
+
+    apply_slippage(fund, expected_out)
+}
+
+/// Reduces an expected amount by the fund's slippage tolerance
+pub fn apply_slippage(fund: &Fund, amount: u64) -> Result<u64, ProgramError> {
+    let reduced = amount as u128 * (BPS_DENOMINATOR - fund.max_slippage_bps) as u128 / BPS_DENOMINATOR as u128;
+    u64::try_from(reduced).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Checks a trade's quote notional against the per-trade and per-epoch caps and records it
+pub fn record_trade_notional(fund: &mut Fund, notional: u64, now: i64) -> ProgramResult {
+    if notional > fund.max_trade_notional {
+        return Err(SaturnFundError::TradeNotionalExceeded.into());
+    }
+
+    // Roll over to a fresh epoch once the current one has elapsed
+    if now >= fund.epoch_start.saturating_add(fund.epoch_duration) {
+        fund.epoch_start = now;
+        fund.epoch_notional_traded = 0;
+    }
+
+    let traded = fund.epoch_notional_traded
+        .checked_add(notional)
+        .ok_or(ProgramError::InvalidArgument)?;
+    if traded > fund.max_epoch_notional {
+        return Err(SaturnFundError::EpochNotionalExceeded.into());
+    }
+
+    fund.epoch_notional_traded = traded;
+    Ok(())
+}
+
+/// Fails with `SlippageExceeded` when a destination account received less than `min_out`
+pub fn verify_amount_received(balance_before: u64, balance_after: u64, min_out: u64) -> ProgramResult {
+    let received = balance_after.saturating_sub(balance_before);
+    if received < min_out {
+        msg!("Received {} but required at least {}", received, min_out);
+        return Err(SaturnFundError::SlippageExceeded.into());
+    }
+    Ok(())
+}
@@ -17,6 +17,9 @@ pub fn handler(ctx: Context<UpdateAllocationPda>, new_allocation: Allocation) ->
     allocation_pda.baseline_amount_percentage = new_allocation.baseline_amount_percentage;
     allocation_pda.liquidity_pool_reward_percentage = new_allocation.liquidity_pool_reward_percentage;
     allocation_pda.liquidity_pool_reward_destination = new_allocation.liquidity_pool_reward_destination;
+    allocation_pda.liquidity_target_percentage = new_allocation.liquidity_target_percentage;
+    allocation_pda.liquidity_band_percentage = new_allocation.liquidity_band_percentage;
+    allocation_pda.liquidity_pools = new_allocation.liquidity_pools;
 
     Ok(())
 }
@@ -2,6 +2,9 @@ use anchor_lang::prelude::*;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Denominator for all basis-point values stored on-chain
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
 #[program]
 pub mod saturn_fund {
     use super::*;
@@ -12,6 +15,12 @@ pub mod saturn_fund {
 }
 
 mod instructions;
+pub mod error;
+
+pub use instructions::state;
+pub use instructions::raydium_integration;
+pub use instructions::openbook;
+pub use instructions::calculate_price_of_fund;
 
 use anchor_lang::solana_program::{
     entrypoint::ProgramResult,
@@ -21,15 +30,28 @@ use anchor_lang::solana_program::{
 };
 
 use crate::instructions::{
-    manage_holdings::sell_tokens,
-    mint_management::create_mint,
+    manage_holdings::{sell_tokens, plan_rebalance, create_allocation, set_allocation},
+    mint_management::{create_mint, create_share_mint_2022, set_share_transfer_fee, collect_share_transfer_fees, update_share_metadata_uri, renounce_freeze_authority},
     fund_management::ensure_solana_balance,
     burn_token::handler as burn_token_handler,
     mint_token::handler as mint_token_handler,
     calculate_price_of_fund::handler as calculate_price_handler,
+    trade_limits::{update_trade_limits, set_keeper, set_oracle_config},
+    raydium_integration::{deposit_liquidity, withdraw_liquidity, ensure_liquidity_representation},
+    openbook::{register_market, settle_holding_funds, cancel_holding_order},
+    aggregator::swap_via_aggregator,
+    raydium_farm::{stake_lp, unstake_lp},
+    fund_operation::{initialize_fund, create_holding_vaults, close_holding_vault},
+    collect_liquidity_pool_rewards::{collect_liquidity_pool_rewards, set_reward_recipients, set_compounding, set_profit_vesting_period},
+    fee_handling::{accrue_fees, set_management_fee, set_performance_fee, set_fee_rates, set_fee_schedule, open_user_position, set_referral_fee},
+    fee_distribution::{create_fee_vault, set_fee_recipients, distribute_fees, create_fee_totals},
+    referral::{register_referrer, claim_referral_fees},
+    allowlist::{set_restricted, set_compliance_authority, add_to_allowlist, remove_from_allowlist, initialize_allowlist_hook, transfer_hook_execute},
 };
 
 use solana_program::entrypoint;
+use spl_discriminator::SplDiscriminate;
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
 
 entrypoint!(process_instruction);
 
@@ -38,12 +60,62 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    // Token-2022 calls the share mint's transfer hook with the interface's 8-byte discriminator
+    if instruction_data.starts_with(ExecuteInstruction::SPL_DISCRIMINATOR_SLICE) {
+        return transfer_hook_execute(program_id, accounts, instruction_data);
+    }
+
     match instruction_data[0] {
         0 => mint_token_handler(program_id, accounts, instruction_data),
         1 => burn_token_handler(program_id, accounts, instruction_data),
         2 => ensure_solana_balance(program_id, accounts, instruction_data),
         3 => sell_tokens(program_id, accounts, instruction_data),
         4 => calculate_price_handler(program_id, accounts, instruction_data),
+        5 => plan_rebalance(program_id, accounts, instruction_data),
+        6 => update_trade_limits(program_id, accounts, instruction_data),
+        7 => deposit_liquidity(program_id, accounts, instruction_data),
+        8 => withdraw_liquidity(program_id, accounts, instruction_data),
+        9 => ensure_liquidity_representation(program_id, accounts, instruction_data),
+        10 => register_market(program_id, accounts, instruction_data),
+        11 => settle_holding_funds(program_id, accounts, instruction_data),
+        12 => cancel_holding_order(program_id, accounts, instruction_data),
+        13 => set_keeper(program_id, accounts, instruction_data),
+        14 => swap_via_aggregator(program_id, accounts, instruction_data),
+        15 => stake_lp(program_id, accounts, instruction_data),
+        16 => unstake_lp(program_id, accounts, instruction_data),
+        17 => collect_liquidity_pool_rewards(program_id, accounts, instruction_data),
+        18 => set_reward_recipients(program_id, accounts, instruction_data),
+        19 => set_compounding(program_id, accounts, instruction_data),
+        20 => set_profit_vesting_period(program_id, accounts, instruction_data),
+        21 => accrue_fees(program_id, accounts, instruction_data),
+        22 => set_management_fee(program_id, accounts, instruction_data),
+        23 => set_performance_fee(program_id, accounts, instruction_data),
+        24 => set_fee_rates(program_id, accounts, instruction_data),
+        26 => set_fee_schedule(program_id, accounts, instruction_data),
+        27 => open_user_position(program_id, accounts, instruction_data),
+        28 => create_fee_vault(program_id, accounts, instruction_data),
+        29 => set_fee_recipients(program_id, accounts, instruction_data),
+        30 => distribute_fees(program_id, accounts, instruction_data),
+        31 => set_referral_fee(program_id, accounts, instruction_data),
+        32 => register_referrer(program_id, accounts, instruction_data),
+        33 => claim_referral_fees(program_id, accounts, instruction_data),
+        34 => set_restricted(program_id, accounts, instruction_data),
+        35 => set_compliance_authority(program_id, accounts, instruction_data),
+        36 => add_to_allowlist(program_id, accounts, instruction_data),
+        37 => remove_from_allowlist(program_id, accounts, instruction_data),
+        38 => create_share_mint_2022(program_id, accounts, instruction_data),
+        39 => set_share_transfer_fee(program_id, accounts, instruction_data),
+        40 => collect_share_transfer_fees(program_id, accounts, instruction_data),
+        41 => initialize_allowlist_hook(program_id, accounts, instruction_data),
+        42 => update_share_metadata_uri(program_id, accounts, instruction_data),
+        43 => renounce_freeze_authority(program_id, accounts, instruction_data),
+        44 => create_holding_vaults(program_id, accounts, instruction_data),
+        45 => close_holding_vault(program_id, accounts, instruction_data),
+        46 => create_allocation(program_id, accounts, instruction_data),
+        47 => set_allocation(program_id, accounts, instruction_data),
+        48 => initialize_fund(program_id, accounts, instruction_data),
+        49 => set_oracle_config(program_id, accounts, instruction_data),
+        50 => create_fee_totals(program_id, accounts, instruction_data),
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! Sizes OpenBook IOC orders with `ioc_order` and fills them the way the DEX matches a taker
+//! against a single resting level: whole lots only, with the taker fee rounded up.
+
+use saturn_fund::openbook::{ioc_order, IocOrder, OPENBOOK_TAKER_FEE_BPS};
+use saturn_fund::state::market::HoldingMarket;
+use serum_dex::matching::Side;
+use solana_sdk::pubkey::Pubkey;
+
+const BASE_LOT_SIZE: u64 = 1_000;
+const QUOTE_LOT_SIZE: u64 = 10;
+const BPS: u128 = 10_000;
+
+fn holding_market() -> HoldingMarket {
+    HoldingMarket {
+        fund: Pubkey::new_unique(),
+        base_mint: Pubkey::new_unique(),
+        quote_mint: Pubkey::new_unique(),
+        dex_program: Pubkey::new_unique(),
+        market: Pubkey::new_unique(),
+        bids: Pubkey::new_unique(),
+        asks: Pubkey::new_unique(),
+        request_queue: Pubkey::new_unique(),
+        event_queue: Pubkey::new_unique(),
+        coin_vault: Pubkey::new_unique(),
+        pc_vault: Pubkey::new_unique(),
+        vault_signer: Pubkey::new_unique(),
+        open_orders: Pubkey::new_unique(),
+        fund_base_vault: Pubkey::new_unique(),
+        fund_quote_vault: Pubkey::new_unique(),
+        base_lot_size: BASE_LOT_SIZE,
+        quote_lot_size: QUOTE_LOT_SIZE,
+        bump: 0,
+        open_orders_bump: 0,
+    }
+}
+
+fn taker_fee(quote: u128) -> u128 {
+    (quote * OPENBOOK_TAKER_FEE_BPS as u128 + BPS - 1) / BPS
+}
+
+/// Base received by a bid taking asks resting at `price_lots`
+fn fill_bid(order: &IocOrder, price_lots: u64) -> u64 {
+    assert!(price_lots <= order.limit_price_lots);
+    let lot_cost = price_lots as u128 * QUOTE_LOT_SIZE as u128;
+    let mut lots = (order.max_base_qty / BASE_LOT_SIZE) as u128;
+    while lots > 0 && lots * lot_cost + taker_fee(lots * lot_cost) > order.max_quote_qty as u128 {
+        lots = (order.max_quote_qty as u128 * BPS / (BPS + OPENBOOK_TAKER_FEE_BPS as u128) / lot_cost).min(lots - 1);
+    }
+    (lots * BASE_LOT_SIZE as u128) as u64
+}
+
+/// Quote received by an ask taking bids resting at `price_lots`
+fn fill_ask(order: &IocOrder, price_lots: u64) -> u64 {
+    assert!(price_lots >= order.limit_price_lots);
+    let lots = (order.max_base_qty / BASE_LOT_SIZE) as u128;
+    let quote = lots * price_lots as u128 * QUOTE_LOT_SIZE as u128;
+    (quote - taker_fee(quote)) as u64
+}
+
+#[test]
+fn bid_fills_minimum_that_is_not_lot_aligned() {
+    let market = holding_market();
+    let amount_in = 1_000_000;
+    let minimum_amount_out = 2_500; // Two and a half lots
+
+    let order = ioc_order(&market, Side::Bid, amount_in, minimum_amount_out).unwrap();
+
+    assert_eq!(order.max_quote_qty, amount_in);
+    assert!(fill_bid(&order, order.limit_price_lots) >= minimum_amount_out);
+    assert!(fill_bid(&order, order.limit_price_lots / 2) >= minimum_amount_out);
+}
+
+#[test]
+fn ask_fills_minimum_after_taker_fee() {
+    let market = holding_market();
+    let amount_in = 5_500; // Five whole lots and a remainder that stays in the vault
+    let minimum_amount_out = 100_000;
+
+    let order = ioc_order(&market, Side::Ask, amount_in, minimum_amount_out).unwrap();
+
+    assert_eq!(order.max_base_qty, 5_000);
+    assert!(fill_ask(&order, order.limit_price_lots) >= minimum_amount_out);
+}
+
+#[test]
+fn ask_below_one_lot_is_rejected() {
+    let market = holding_market();
+
+    assert!(ioc_order(&market, Side::Ask, BASE_LOT_SIZE - 1, 1).is_err());
+}
@@ -0,0 +1,316 @@
+//! Runs `swap_base_in_instruction` against a local fixture of a Raydium AMM v4 pool.
+//! The fixture program stands in for the AMM at its mainnet address: it accepts only the
+//! `swap_base_in` account list and encoding, and swaps at the pool's constant-product price.
+//! The fund's own swap, `swap_via_raydium`, is run from the fund program's address so it signs
+//! for vaults owned by the fund authority PDA.
+
+use std::convert::{TryFrom, TryInto};
+use anchor_lang::Program;
+use saturn_fund::raydium_integration::{raydium_amm_v4, swap_base_in_instruction, swap_via_raydium, SwapBaseInKeys, SwapViaRaydium};
+use saturn_fund::state::fund::FUND_AUTHORITY_SEED;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+const SWAP_BASE_IN_TAG: u8 = 9;
+const SWAP_BASE_IN_ACCOUNTS: usize = 18;
+const EXCEEDED_SLIPPAGE: u32 = 30; // Raydium's error code for a missed minimum
+
+const COIN_RESERVE: u64 = 1_000_000;
+const PC_RESERVE: u64 = 2_000_000;
+const USER_COIN: u64 = 50_000;
+const FUND_COIN: u64 = 80_000;
+
+fn process_fixture_amm(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != 17 || data[0] != SWAP_BASE_IN_TAG || accounts.len() != SWAP_BASE_IN_ACCOUNTS {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+
+    let token_program = &accounts[0];
+    let amm_authority = &accounts[2];
+    let pool_coin_vault = &accounts[5];
+    let pool_pc_vault = &accounts[6];
+    let source = &accounts[15];
+    let destination = &accounts[16];
+    let source_owner = &accounts[17];
+
+    let (authority, bump) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    if *amm_authority.key != authority || !source_owner.is_signer || *token_program.key != spl_token::id() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let coin_reserve = TokenAccount::unpack(&pool_coin_vault.data.borrow())?.amount;
+    let pc_reserve = TokenAccount::unpack(&pool_pc_vault.data.borrow())?.amount;
+    let amount_out = (pc_reserve as u128 * amount_in as u128 / (coin_reserve as u128 + amount_in as u128)) as u64;
+    if amount_out < minimum_amount_out {
+        return Err(ProgramError::Custom(EXCEEDED_SLIPPAGE));
+    }
+
+    invoke(
+        &spl_token::instruction::transfer(token_program.key, source.key, pool_coin_vault.key, source_owner.key, &[], amount_in)?,
+        &[source.clone(), pool_coin_vault.clone(), source_owner.clone(), token_program.clone()],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::transfer(token_program.key, pool_pc_vault.key, destination.key, amm_authority.key, &[], amount_out)?,
+        &[pool_pc_vault.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump]]],
+    )
+}
+
+// Fund program entry for the swap: the fund address, then the `SwapViaRaydium` accounts in
+// declaration order, with `amount_in` and `minimum_amount_out` as data
+fn process_fund_swap(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[0..8].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(data[8..16].try_into().unwrap());
+
+    let fund = accounts[0].key;
+    let (_, bump) = Pubkey::find_program_address(&[FUND_AUTHORITY_SEED, fund.as_ref()], program_id);
+    let swap_accounts = SwapViaRaydium {
+        amm_program: accounts[1].clone(),
+        amm: accounts[2].clone(),
+        amm_authority: accounts[3].clone(),
+        amm_open_orders: accounts[4].clone(),
+        amm_target_orders: accounts[5].clone(),
+        pool_coin_vault: accounts[6].clone(),
+        pool_pc_vault: accounts[7].clone(),
+        serum_program: accounts[8].clone(),
+        serum_market: accounts[9].clone(),
+        serum_bids: accounts[10].clone(),
+        serum_asks: accounts[11].clone(),
+        serum_event_queue: accounts[12].clone(),
+        serum_coin_vault: accounts[13].clone(),
+        serum_pc_vault: accounts[14].clone(),
+        serum_vault_signer: accounts[15].clone(),
+        source_token_account: accounts[16].clone(),
+        destination_token_account: accounts[17].clone(),
+        source_owner: accounts[18].clone(),
+        token_program: Program::try_from(&accounts[19])?,
+    };
+
+    swap_via_raydium(&swap_accounts, amount_in, minimum_amount_out, &[&[FUND_AUTHORITY_SEED, fund.as_ref(), &[bump]]])
+}
+
+struct Pool {
+    keys: SwapBaseInKeys,
+    user: Keypair,
+}
+
+struct FundVaults {
+    fund: Pubkey,
+    authority: Pubkey,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+}
+
+fn add_mint(program_test: &mut ProgramTest, authority: &Pubkey) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(*authority),
+            supply: u64::MAX / 2,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(mint, account(data));
+    mint
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(address, account(data));
+    address
+}
+
+fn account(data: Vec<u8>) -> solana_sdk::account::Account {
+    solana_sdk::account::Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+async fn start_pool() -> (ProgramTestContext, Pool, FundVaults) {
+    let mut program_test = ProgramTest::new("raydium_amm_fixture", raydium_amm_v4::ID, processor!(process_fixture_amm));
+    program_test.add_program("saturn_fund", saturn_fund::ID, processor!(process_fund_swap));
+    let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_amm_v4::ID);
+    let user = Keypair::new();
+
+    let coin_mint = add_mint(&mut program_test, &amm_authority);
+    let pc_mint = add_mint(&mut program_test, &amm_authority);
+    let keys = SwapBaseInKeys {
+        token_program: spl_token::id(),
+        amm: Pubkey::new_unique(),
+        amm_authority,
+        amm_open_orders: Pubkey::new_unique(),
+        amm_target_orders: Pubkey::new_unique(),
+        pool_coin_vault: add_token_account(&mut program_test, &coin_mint, &amm_authority, COIN_RESERVE),
+        pool_pc_vault: add_token_account(&mut program_test, &pc_mint, &amm_authority, PC_RESERVE),
+        serum_program: Pubkey::new_unique(),
+        serum_market: Pubkey::new_unique(),
+        serum_bids: Pubkey::new_unique(),
+        serum_asks: Pubkey::new_unique(),
+        serum_event_queue: Pubkey::new_unique(),
+        serum_coin_vault: Pubkey::new_unique(),
+        serum_pc_vault: Pubkey::new_unique(),
+        serum_vault_signer: Pubkey::new_unique(),
+        source_token_account: add_token_account(&mut program_test, &coin_mint, &user.pubkey(), USER_COIN),
+        destination_token_account: add_token_account(&mut program_test, &pc_mint, &user.pubkey(), 0),
+        source_owner: user.pubkey(),
+    };
+
+    let fund = Pubkey::new_unique();
+    let (authority, _) = Pubkey::find_program_address(&[FUND_AUTHORITY_SEED, fund.as_ref()], &saturn_fund::ID);
+    let vaults = FundVaults {
+        fund,
+        authority,
+        coin_vault: add_token_account(&mut program_test, &coin_mint, &authority, FUND_COIN),
+        pc_vault: add_token_account(&mut program_test, &pc_mint, &authority, 0),
+    };
+
+    (program_test.start_with_context().await, Pool { keys, user }, vaults)
+}
+
+async fn swap(context: &mut ProgramTestContext, pool: &Pool, amount_in: u64, minimum_amount_out: u64) -> Result<(), String> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[swap_base_in_instruction(&pool.keys, amount_in, minimum_amount_out)],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &pool.user],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await.map_err(|err| err.to_string())
+}
+
+async fn fund_swap(
+    context: &mut ProgramTestContext,
+    pool: &Pool,
+    vaults: &FundVaults,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<(), String> {
+    let keys = &pool.keys;
+    let mut data = Vec::with_capacity(16);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    let instruction = Instruction {
+        program_id: saturn_fund::ID,
+        accounts: vec![
+            AccountMeta::new_readonly(vaults.fund, false),
+            AccountMeta::new_readonly(raydium_amm_v4::ID, false),
+            AccountMeta::new(keys.amm, false),
+            AccountMeta::new_readonly(keys.amm_authority, false),
+            AccountMeta::new(keys.amm_open_orders, false),
+            AccountMeta::new(keys.amm_target_orders, false),
+            AccountMeta::new(keys.pool_coin_vault, false),
+            AccountMeta::new(keys.pool_pc_vault, false),
+            AccountMeta::new_readonly(keys.serum_program, false),
+            AccountMeta::new(keys.serum_market, false),
+            AccountMeta::new(keys.serum_bids, false),
+            AccountMeta::new(keys.serum_asks, false),
+            AccountMeta::new(keys.serum_event_queue, false),
+            AccountMeta::new(keys.serum_coin_vault, false),
+            AccountMeta::new(keys.serum_pc_vault, false),
+            AccountMeta::new_readonly(keys.serum_vault_signer, false),
+            AccountMeta::new(vaults.coin_vault, false),
+            AccountMeta::new(vaults.pc_vault, false),
+            AccountMeta::new_readonly(vaults.authority, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await.map_err(|err| err.to_string())
+}
+
+async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let account = context.banks_client.get_account(*address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn swap_base_in_pays_out_at_pool_price() {
+    let (mut context, pool, _) = start_pool().await;
+    let amount_in = 10_000;
+    let expected_out = PC_RESERVE * amount_in / (COIN_RESERVE + amount_in);
+
+    swap(&mut context, &pool, amount_in, expected_out).await.unwrap();
+
+    assert_eq!(token_balance(&mut context, &pool.keys.source_token_account).await, USER_COIN - amount_in);
+    assert_eq!(token_balance(&mut context, &pool.keys.destination_token_account).await, expected_out);
+    assert_eq!(token_balance(&mut context, &pool.keys.pool_coin_vault).await, COIN_RESERVE + amount_in);
+    assert_eq!(token_balance(&mut context, &pool.keys.pool_pc_vault).await, PC_RESERVE - expected_out);
+}
+
+#[tokio::test]
+async fn swap_base_in_rejects_minimum_above_quote() {
+    let (mut context, pool, _) = start_pool().await;
+    let amount_in = 10_000;
+    let expected_out = PC_RESERVE * amount_in / (COIN_RESERVE + amount_in);
+
+    assert!(swap(&mut context, &pool, amount_in, expected_out + 1).await.is_err());
+    assert_eq!(token_balance(&mut context, &pool.keys.source_token_account).await, USER_COIN);
+    assert_eq!(token_balance(&mut context, &pool.keys.destination_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn fund_swap_signs_for_its_vaults_and_receives_the_output() {
+    let (mut context, pool, vaults) = start_pool().await;
+    let amount_in = 20_000;
+    let expected_out = PC_RESERVE * amount_in / (COIN_RESERVE + amount_in);
+
+    fund_swap(&mut context, &pool, &vaults, amount_in, expected_out).await.unwrap();
+
+    assert_eq!(token_balance(&mut context, &vaults.coin_vault).await, FUND_COIN - amount_in);
+    assert_eq!(token_balance(&mut context, &vaults.pc_vault).await, expected_out);
+    assert_eq!(token_balance(&mut context, &pool.keys.pool_coin_vault).await, COIN_RESERVE + amount_in);
+    assert_eq!(token_balance(&mut context, &pool.keys.pool_pc_vault).await, PC_RESERVE - expected_out);
+}
+
+#[tokio::test]
+async fn fund_swap_below_minimum_leaves_the_fund_untouched() {
+    let (mut context, pool, vaults) = start_pool().await;
+    let amount_in = 20_000;
+    let expected_out = PC_RESERVE * amount_in / (COIN_RESERVE + amount_in);
+
+    assert!(fund_swap(&mut context, &pool, &vaults, amount_in, expected_out + 1).await.is_err());
+    assert_eq!(token_balance(&mut context, &vaults.coin_vault).await, FUND_COIN);
+    assert_eq!(token_balance(&mut context, &vaults.pc_vault).await, 0);
+}
@@ -0,0 +1,98 @@
+//! Prices deposits and redemptions off the fund's value per share, where compounded rewards
+//! only count towards the value once they have vested.
+
+use saturn_fund::calculate_price_of_fund::{shares_for_deposit, value_of_shares};
+use saturn_fund::state::fund::Fund;
+use solana_sdk::pubkey::Pubkey;
+
+const VESTING_PERIOD: i64 = 1_000;
+const HARVEST_AT: i64 = 10_000;
+
+fn vesting_fund(locked_profit: u64) -> Fund {
+    Fund {
+        owner: Pubkey::new_unique(),
+        authority_bump: 255,
+        keeper: Pubkey::new_unique(),
+        reward_recipients: Vec::new(),
+        compound_rewards: true,
+        profit_vesting_period: VESTING_PERIOD,
+        locked_profit,
+        last_harvest: HARVEST_AT,
+        share_mint: Pubkey::new_unique(),
+        fee_recipient: Pubkey::new_unique(),
+        management_fee_bps: 0,
+        last_fee_accrual: HARVEST_AT,
+        performance_fee_bps: 0,
+        crystallization_interval: 0,
+        high_water_mark: 0,
+        last_crystallization: 0,
+        mint_fee_bps: 0,
+        redeem_fee_bps: 0,
+        transfer_fee_bps: 0,
+        mint_fee_tiers: Vec::new(),
+        redeem_fee_schedule: Vec::new(),
+        fee_vault: Pubkey::new_unique(),
+        fee_recipients: Vec::new(),
+        referral_fee_bps: 0,
+        referral_fees_owed: 0,
+        restricted: false,
+        compliance_authority: Pubkey::new_unique(),
+        max_slippage_bps: 0,
+        max_trade_notional: 0,
+        max_epoch_notional: 0,
+        epoch_duration: 0,
+        epoch_start: 0,
+        epoch_notional_traded: 0,
+        oracle_program: Pubkey::new_unique(),
+        max_oracle_age: 0,
+        staked_lp: Vec::new(),
+    }
+}
+
+// Mirrors `get_fund_value`: gross holdings and liquidity less the profit still vesting
+fn fund_value(fund: &Fund, gross_value: u64, now: i64) -> u64 {
+    gross_value.saturating_sub(fund.current_locked_profit(now))
+}
+
+#[test]
+fn vesting_profit_raises_the_redemption_amount_over_time() {
+    // 1_000_000 of value backing 1_000 shares, plus 100_000 of freshly compounded rewards
+    let fund = vesting_fund(100_000);
+    let gross_value = 1_100_000;
+    let supply = 1_000;
+
+    let at_harvest = value_of_shares(fund_value(&fund, gross_value, HARVEST_AT), supply, 100).unwrap();
+    let half_vested = value_of_shares(fund_value(&fund, gross_value, HARVEST_AT + VESTING_PERIOD / 2), supply, 100).unwrap();
+    let fully_vested = value_of_shares(fund_value(&fund, gross_value, HARVEST_AT + VESTING_PERIOD), supply, 100).unwrap();
+
+    assert_eq!(at_harvest, 100_000);
+    assert_eq!(half_vested, 105_000);
+    assert_eq!(fully_vested, 110_000);
+}
+
+#[test]
+fn deposits_during_vesting_do_not_buy_locked_profit() {
+    let fund = vesting_fund(100_000);
+    let gross_value = 1_100_000;
+    let supply = 1_000;
+
+    // Right after the harvest the locked rewards are excluded, so the deposit buys at the old price
+    let shares = shares_for_deposit(fund_value(&fund, gross_value, HARVEST_AT), supply, 100_000).unwrap();
+    assert_eq!(shares, 100);
+
+    // Once vested, the same deposit buys fewer shares
+    let shares = shares_for_deposit(fund_value(&fund, gross_value, HARVEST_AT + VESTING_PERIOD), supply, 110_000).unwrap();
+    assert_eq!(shares, 100);
+    let shares = shares_for_deposit(fund_value(&fund, gross_value, HARVEST_AT + VESTING_PERIOD), supply, 100_000).unwrap();
+    assert_eq!(shares, 90);
+}
+
+#[test]
+fn first_deposit_is_issued_at_one_share_per_unit_of_value() {
+    assert_eq!(shares_for_deposit(0, 0, 5_000).unwrap(), 5_000);
+}
+
+#[test]
+fn redeeming_more_than_the_supply_is_rejected() {
+    assert!(value_of_shares(1_000, 10, 11).is_err());
+}